@@ -0,0 +1,546 @@
+//! Writing the topology, with each host's attributes and layout position, as GraphML, GEXF, DOT or
+//! petgraph's JSON, for graph tools such as Gephi, yEd and Graphviz.
+
+use crate::layout::{Layout, Position};
+use crate::model::{SimpleHost, SimpleLink};
+use crate::topology::Topology;
+use petgraph::stable_graph::StableGraph;
+use petgraph::Undirected;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// Graph file formats the topology can be written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    GraphMl,
+    Gexf,
+    Dot,
+    /// petgraph's serde representation of the graph.
+    Json,
+}
+
+impl ExportFormat {
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "graphml" => Some(ExportFormat::GraphMl),
+            "gexf" => Some(ExportFormat::Gexf),
+            "dot" | "gv" => Some(ExportFormat::Dot),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A node as written to exported files: the host's attributes and its current position.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportNode {
+    pub address: String,
+    pub scanned: bool,
+    pub hostnames: Vec<String>,
+    pub os: Option<String>,
+    pub os_family: Option<String>,
+    pub rtt: Option<f32>,
+    pub ports: Vec<ExportPort>,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportPort {
+    pub protocol: String,
+    pub portid: u16,
+    pub state: String,
+    pub service: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+}
+
+/// An edge as written to exported files.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportLink {
+    pub traces: Vec<String>,
+    pub rtt_delta: Option<f32>,
+}
+
+impl ExportNode {
+    fn from_host(host: &SimpleHost, [x, y, z]: Position) -> Self {
+        Self {
+            address: host.main_addr.to_string(),
+            scanned: host.scanned,
+            hostnames: host
+                .hostnames
+                .iter()
+                .map(|hostname| hostname.name.clone())
+                .collect(),
+            os: host.os_guess.as_ref().map(|os| os.name().to_string()),
+            os_family: host.os_guess.as_ref().map(|os| os.family().to_string()),
+            rtt: host.rtt,
+            ports: host
+                .ports
+                .iter()
+                .map(|port| ExportPort {
+                    protocol: port.protocol.clone(),
+                    portid: port.portid,
+                    state: port.state.clone(),
+                    service: port.service.clone(),
+                    product: port.product.clone(),
+                    version: port.version.clone(),
+                })
+                .collect(),
+            x,
+            y,
+            z,
+        }
+    }
+
+    /// Open ports as a single comma-separated string, for formats without list attributes.
+    fn open_ports_summary(&self) -> String {
+        self.ports
+            .iter()
+            .filter(|port| port.state == "open")
+            .map(|port| match &port.service {
+                Some(service) => format!("{}/{} {service}", port.portid, port.protocol),
+                None => format!("{}/{}", port.portid, port.protocol),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl ExportLink {
+    fn from_link(link: &SimpleLink) -> Self {
+        Self {
+            traces: link.traces.iter().map(|trace| trace.to_string()).collect(),
+            rtt_delta: link.rtt_delta,
+        }
+    }
+}
+
+/// Copy the topology and its layout into a graph of export records, keeping the topology's indices.
+pub fn to_export_graph(
+    topology: &Topology,
+    layout: &Layout,
+) -> StableGraph<ExportNode, ExportLink, Undirected> {
+    topology.graph.map(
+        |host_index, host| ExportNode::from_host(host, layout.position(host_index)),
+        |_, link| ExportLink::from_link(link),
+    )
+}
+
+/// Write the topology, with node attributes and current positions, in the given format.
+pub fn export_graph<W: Write>(
+    topology: &Topology,
+    layout: &Layout,
+    format: ExportFormat,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let export = to_export_graph(topology, layout);
+    match format {
+        ExportFormat::GraphMl => write_graphml(&export, writer),
+        ExportFormat::Gexf => write_gexf(&export, writer),
+        ExportFormat::Dot => write_dot(&export, writer),
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &export)?;
+            writeln!(writer)?;
+            Ok(())
+        }
+    }
+}
+
+fn write_graphml<W: Write>(
+    graph: &StableGraph<ExportNode, ExportLink, Undirected>,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    for (id, kind) in [
+        ("address", "string"),
+        ("scanned", "boolean"),
+        ("hostnames", "string"),
+        ("os", "string"),
+        ("os_family", "string"),
+        ("rtt", "double"),
+        ("ports", "string"),
+        ("x", "double"),
+        ("y", "double"),
+        ("z", "double"),
+    ] {
+        writeln!(
+            writer,
+            r#"  <key id="{id}" for="node" attr.name="{id}" attr.type="{kind}"/>"#
+        )?;
+    }
+    writeln!(
+        writer,
+        r#"  <key id="traces" for="edge" attr.name="traces" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="trace_count" for="edge" attr.name="trace_count" attr.type="int"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="rtt_delta" for="edge" attr.name="rtt_delta" attr.type="double"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <graph id="topology" edgedefault="undirected">"#
+    )?;
+
+    for node_index in graph.node_indices() {
+        let node = &graph[node_index];
+        writeln!(writer, r#"    <node id="n{}">"#, node_index.index())?;
+        let mut data = vec![
+            ("address", node.address.clone()),
+            ("scanned", node.scanned.to_string()),
+            ("hostnames", node.hostnames.join(";")),
+            ("ports", node.open_ports_summary()),
+            ("x", node.x.to_string()),
+            ("y", node.y.to_string()),
+            ("z", node.z.to_string()),
+        ];
+        if let Some(os) = &node.os {
+            data.push(("os", os.clone()));
+        }
+        if let Some(os_family) = &node.os_family {
+            data.push(("os_family", os_family.clone()));
+        }
+        if let Some(rtt) = node.rtt {
+            data.push(("rtt", rtt.to_string()));
+        }
+        for (key, value) in data {
+            writeln!(
+                writer,
+                r#"      <data key="{key}">{}</data>"#,
+                escape_xml(&value)
+            )?;
+        }
+        writeln!(writer, "    </node>")?;
+    }
+
+    for edge_index in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge_index).unwrap();
+        let link = &graph[edge_index];
+        writeln!(
+            writer,
+            r#"    <edge source="n{}" target="n{}">"#,
+            source.index(),
+            target.index()
+        )?;
+        writeln!(
+            writer,
+            r#"      <data key="traces">{}</data>"#,
+            escape_xml(&link.traces.join(";"))
+        )?;
+        writeln!(
+            writer,
+            r#"      <data key="trace_count">{}</data>"#,
+            link.traces.len()
+        )?;
+        if let Some(rtt_delta) = link.rtt_delta {
+            writeln!(writer, r#"      <data key="rtt_delta">{rtt_delta}</data>"#)?;
+        }
+        writeln!(writer, "    </edge>")?;
+    }
+
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    Ok(())
+}
+
+fn write_gexf<W: Write>(
+    graph: &StableGraph<ExportNode, ExportLink, Undirected>,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gexf xmlns="http://gexf.net/1.3" xmlns:viz="http://gexf.net/1.3/viz" version="1.3">"#
+    )?;
+    writeln!(writer, r#"  <graph defaultedgetype="undirected">"#)?;
+
+    writeln!(writer, r#"    <attributes class="node">"#)?;
+    for (id, kind) in [
+        ("address", "string"),
+        ("scanned", "boolean"),
+        ("hostnames", "string"),
+        ("os", "string"),
+        ("os_family", "string"),
+        ("rtt", "double"),
+        ("ports", "string"),
+    ] {
+        writeln!(
+            writer,
+            r#"      <attribute id="{id}" title="{id}" type="{kind}"/>"#
+        )?;
+    }
+    writeln!(writer, "    </attributes>")?;
+    writeln!(writer, r#"    <attributes class="edge">"#)?;
+    writeln!(
+        writer,
+        r#"      <attribute id="traces" title="traces" type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"      <attribute id="rtt_delta" title="rtt_delta" type="double"/>"#
+    )?;
+    writeln!(writer, "    </attributes>")?;
+
+    writeln!(writer, "    <nodes>")?;
+    for node_index in graph.node_indices() {
+        let node = &graph[node_index];
+        let label = node.hostnames.first().unwrap_or(&node.address);
+        writeln!(
+            writer,
+            r#"      <node id="{}" label="{}">"#,
+            node_index.index(),
+            escape_xml(label)
+        )?;
+        writeln!(writer, "        <attvalues>")?;
+        let mut values = vec![
+            ("address", node.address.clone()),
+            ("scanned", node.scanned.to_string()),
+            ("hostnames", node.hostnames.join(";")),
+            ("ports", node.open_ports_summary()),
+        ];
+        if let Some(os) = &node.os {
+            values.push(("os", os.clone()));
+        }
+        if let Some(os_family) = &node.os_family {
+            values.push(("os_family", os_family.clone()));
+        }
+        if let Some(rtt) = node.rtt {
+            values.push(("rtt", rtt.to_string()));
+        }
+        for (key, value) in values {
+            writeln!(
+                writer,
+                r#"          <attvalue for="{key}" value="{}"/>"#,
+                escape_xml(&value)
+            )?;
+        }
+        writeln!(writer, "        </attvalues>")?;
+        writeln!(
+            writer,
+            r#"        <viz:position x="{}" y="{}" z="{}"/>"#,
+            node.x, node.y, node.z
+        )?;
+        writeln!(writer, "      </node>")?;
+    }
+    writeln!(writer, "    </nodes>")?;
+
+    writeln!(writer, "    <edges>")?;
+    for edge_index in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge_index).unwrap();
+        let link = &graph[edge_index];
+        writeln!(
+            writer,
+            r#"      <edge id="{}" source="{}" target="{}" weight="{}">"#,
+            edge_index.index(),
+            source.index(),
+            target.index(),
+            // Links attached for want of a route have no traces, but still join their hosts.
+            link.traces.len().max(1)
+        )?;
+        writeln!(writer, "        <attvalues>")?;
+        writeln!(
+            writer,
+            r#"          <attvalue for="traces" value="{}"/>"#,
+            escape_xml(&link.traces.join(";"))
+        )?;
+        if let Some(rtt_delta) = link.rtt_delta {
+            writeln!(
+                writer,
+                r#"          <attvalue for="rtt_delta" value="{rtt_delta}"/>"#
+            )?;
+        }
+        writeln!(writer, "        </attvalues>")?;
+        writeln!(writer, "      </edge>")?;
+    }
+    writeln!(writer, "    </edges>")?;
+
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</gexf>")?;
+    Ok(())
+}
+
+fn write_dot<W: Write>(
+    graph: &StableGraph<ExportNode, ExportLink, Undirected>,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(writer, "graph topology {{")?;
+    for node_index in graph.node_indices() {
+        let node = &graph[node_index];
+        let label = node.hostnames.first().unwrap_or(&node.address);
+        write!(
+            writer,
+            r#"  n{} [label="{}", address="{}", scanned={}, hostnames="{}", ports="{}", pos="{},{},{}""#,
+            node_index.index(),
+            escape_dot(label),
+            escape_dot(&node.address),
+            node.scanned,
+            escape_dot(&node.hostnames.join(";")),
+            escape_dot(&node.open_ports_summary()),
+            node.x,
+            node.y,
+            node.z
+        )?;
+        if let Some(os) = &node.os {
+            write!(writer, r#", os="{}""#, escape_dot(os))?;
+        }
+        if let Some(rtt) = node.rtt {
+            write!(writer, ", rtt={rtt}")?;
+        }
+        writeln!(writer, "];")?;
+    }
+    for edge_index in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge_index).unwrap();
+        let link = &graph[edge_index];
+        write!(
+            writer,
+            "  n{} -- n{} [traces={}",
+            source.index(),
+            target.index(),
+            link.traces.len()
+        )?;
+        if let Some(rtt_delta) = link.rtt_delta {
+            write!(writer, ", rtt_delta={rtt_delta}")?;
+        }
+        writeln!(writer, "];")?;
+    }
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import;
+
+    /// A gateway and a host behind it, with names and OS strings that need escaping in every format.
+    fn topology() -> Topology {
+        import::import_json(
+            br#"{
+                "hosts": [{
+                    "address": "10.0.0.2",
+                    "hostnames": ["web.example.net"],
+                    "os": "Linux 5.4 \"lab\" <test> & co",
+                    "rtt": 1.5,
+                    "ports": [
+                        { "portid": 443, "service": "https" },
+                        { "portid": 8080, "state": "closed" }
+                    ],
+                    "trace": [
+                        { "ttl": 1, "address": "10.0.0.1" },
+                        { "ttl": 2, "address": "10.0.0.2" }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn export(format: ExportFormat) -> String {
+        let topology = topology();
+        let mut bytes = Vec::new();
+        export_graph(&topology, &Layout::new(&topology), format, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// Fail unless the document parses as XML throughout.
+    fn assert_well_formed(document: &str) {
+        for event in xml::reader::EventReader::new(document.as_bytes()) {
+            event.unwrap();
+        }
+    }
+
+    #[test]
+    fn graphml_carries_host_and_link_attributes() {
+        let graphml = export(ExportFormat::GraphMl);
+        assert_well_formed(&graphml);
+        assert!(graphml.contains(r#"<data key="address">10.0.0.2</data>"#));
+        assert!(graphml.contains(r#"<data key="scanned">true</data>"#));
+        assert!(graphml.contains(r#"<data key="hostnames">web.example.net</data>"#));
+        assert!(graphml.contains(r#"<data key="ports">443/tcp https</data>"#));
+        assert!(graphml
+            .contains(r#"<data key="os">Linux 5.4 &quot;lab&quot; &lt;test&gt; &amp; co</data>"#));
+        assert!(graphml.contains(r#"<data key="rtt">1.5</data>"#));
+        assert!(graphml.contains(r#"<data key="traces">10.0.0.2</data>"#));
+        assert!(graphml.contains(r#"<data key="trace_count">1</data>"#));
+    }
+
+    #[test]
+    fn gexf_carries_host_attributes_and_positions() {
+        let gexf = export(ExportFormat::Gexf);
+        assert_well_formed(&gexf);
+        assert!(gexf.contains(r#"label="web.example.net""#));
+        assert!(gexf.contains(r#"<attvalue for="address" value="10.0.0.2"/>"#));
+        assert!(gexf.contains(r#"<attvalue for="os_family" value="Linux"/>"#));
+        assert!(gexf.contains(
+            r#"<attvalue for="os" value="Linux 5.4 &quot;lab&quot; &lt;test&gt; &amp; co"/>"#
+        ));
+        assert!(gexf.contains(r#"<attvalue for="traces" value="10.0.0.2"/>"#));
+        assert_eq!(gexf.matches("<viz:position ").count(), 3);
+    }
+
+    #[test]
+    fn gexf_weighs_untraced_links() {
+        let mut topology =
+            import::import_json(br#"{ "hosts": [{ "address": "10.0.0.5" }] }"#).unwrap();
+        topology.attach_orphans();
+        let mut bytes = Vec::new();
+        export_graph(
+            &topology,
+            &Layout::new(&topology),
+            ExportFormat::Gexf,
+            &mut bytes,
+        )
+        .unwrap();
+        let gexf = String::from_utf8(bytes).unwrap();
+        assert!(gexf.contains(r#"source="0" target="1" weight="1">"#));
+    }
+
+    #[test]
+    fn dot_quotes_attribute_values() {
+        let dot = export(ExportFormat::Dot);
+        assert!(dot.starts_with("graph topology {"));
+        assert!(dot.contains(r#"label="web.example.net", address="10.0.0.2", scanned=true"#));
+        assert!(dot.contains(r#"os="Linux 5.4 \"lab\" <test> & co""#));
+        assert!(dot.contains("rtt=1.5"));
+        assert_eq!(dot.matches(" -- ").count(), 2);
+        assert!(dot.contains("[traces=1]"));
+    }
+
+    #[test]
+    fn json_is_the_export_graph_serialized() {
+        let json: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json)).unwrap();
+        let nodes = json["nodes"].as_array().unwrap();
+        let host = nodes
+            .iter()
+            .find(|node| node["address"] == "10.0.0.2")
+            .unwrap();
+        assert_eq!(host["os_family"], "Linux");
+        assert_eq!(host["ports"].as_array().unwrap().len(), 2);
+        assert!(host["x"].is_number());
+        assert_eq!(json["edge_property"], "undirected");
+        assert_eq!(json["edges"].as_array().unwrap().len(), 2);
+    }
+}
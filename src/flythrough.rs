@@ -0,0 +1,180 @@
+//! Camera keyframes recorded in the viewer, and rendering the camera moving between them as a numbered PNG sequence.
+
+use crate::import;
+use crate::labels::LabelMode;
+use crate::layout::Layout;
+use crate::render::{self, OrbitCamera, RenderOptions};
+use crate::stream;
+use crate::topology::Topology;
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::f32::consts::{PI, TAU};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_KEYFRAMES_PATH: &str = "flythrough.json";
+const DEFAULT_FRAMES_PER_KEYFRAME: usize = 60;
+
+/// One camera pose, in the same terms as kiss3d's ArcBall.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub target: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+/// Camera keyframes, saved together with the node positions they were recorded over.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Flythrough {
+    #[serde(default = "default_frames_per_keyframe")]
+    pub frames_per_keyframe: usize,
+    pub keyframes: Vec<Keyframe>,
+    /// Node positions by address, so the frames show the layout the keyframes were set on.
+    #[serde(default)]
+    pub layout: BTreeMap<String, [f32; 3]>,
+}
+
+fn default_frames_per_keyframe() -> usize {
+    DEFAULT_FRAMES_PER_KEYFRAME
+}
+
+impl Default for Flythrough {
+    fn default() -> Self {
+        Flythrough {
+            frames_per_keyframe: DEFAULT_FRAMES_PER_KEYFRAME,
+            keyframes: Vec::new(),
+            layout: BTreeMap::new(),
+        }
+    }
+}
+
+impl Flythrough {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    /// Remember where every node currently is.
+    pub fn capture_layout(&mut self, topology: &Topology, layout: &Layout) {
+        self.layout = layout
+            .positions()
+            .map(|(host_index, position)| {
+                (topology.graph[host_index].main_addr.to_string(), position)
+            })
+            .collect();
+    }
+
+    /// Move nodes to their saved positions; nodes missing from the layout stay put.
+    pub fn apply_layout(&self, topology: &Topology, layout: &mut Layout) {
+        for host_index in topology.host_indices() {
+            let addr = topology.graph[host_index].main_addr.to_string();
+            if let Some(position) = self.layout.get(&addr) {
+                layout.set_position(host_index, *position);
+            }
+        }
+    }
+
+    /// The camera for every frame, easing in and out of each keyframe.
+    pub fn cameras(&self, fov: f32) -> Vec<OrbitCamera> {
+        let steps = self.frames_per_keyframe.max(1);
+        let mut cameras: Vec<OrbitCamera> = self
+            .keyframes
+            .windows(2)
+            .flat_map(|pair| {
+                (0..steps).map(move |step| {
+                    let t = step as f32 / steps as f32;
+                    interpolate(&pair[0], &pair[1], t * t * (3.0 - 2.0 * t), fov)
+                })
+            })
+            .collect();
+        if let Some(last) = self.keyframes.last() {
+            cameras.push(interpolate(last, last, 0.0, fov));
+        }
+        cameras
+    }
+}
+
+fn interpolate(from: &Keyframe, to: &Keyframe, t: f32, fov: f32) -> OrbitCamera {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    // Turn the short way round.
+    let yaw_delta = (to.yaw - from.yaw + PI).rem_euclid(TAU) - PI;
+
+    OrbitCamera {
+        target: Point3::new(
+            lerp(from.target[0], to.target[0]),
+            lerp(from.target[1], to.target[1]),
+            lerp(from.target[2], to.target[2]),
+        ),
+        yaw: from.yaw + yaw_delta * t,
+        pitch: lerp(from.pitch, to.pitch),
+        distance: lerp(from.distance, to.distance),
+        fov,
+    }
+}
+
+/// Entry point for `neuromaster flythrough`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut keyframes_path = PathBuf::from(DEFAULT_KEYFRAMES_PATH);
+    let mut frames_per_keyframe = None;
+    let mut width = render::DEFAULT_WIDTH;
+    let mut height = render::DEFAULT_HEIGHT;
+    let mut label_mode = Some(LabelMode::Ip);
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--keyframes" => keyframes_path = PathBuf::from(value(&arg)?),
+            "--frames" => frames_per_keyframe = Some(value(&arg)?.parse()?),
+            "--width" => width = value(&arg)?.parse()?,
+            "--height" => height = value(&arg)?.parse()?,
+            "--labels" => label_mode = Some(render::parse_label_mode(&value(&arg)?)?),
+            "--label-template" => label_mode = Some(LabelMode::Template(value(&arg)?)),
+            "--no-labels" => label_mode = None,
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let output = output.ok_or("flythrough requires an output directory: -o <directory>")?;
+
+    let mut flythrough = Flythrough::load(&keyframes_path)?;
+    if let Some(frames_per_keyframe) = frames_per_keyframe {
+        flythrough.frames_per_keyframe = frames_per_keyframe;
+    }
+    if flythrough.keyframes.is_empty() {
+        return Err(format!("{} has no keyframes", keyframes_path.display()).into());
+    }
+
+    let topology = import::load_inputs(&inputs, stream::report_progress)?.topology;
+    stream::end_progress();
+    let mut layout = Layout::new(&topology);
+    if flythrough.layout.is_empty() {
+        layout.settle();
+    } else {
+        flythrough.apply_layout(&topology, &mut layout);
+    }
+
+    fs::create_dir_all(&output)?;
+    let cameras = flythrough.cameras(render::DEFAULT_FOV);
+    let mut options = RenderOptions {
+        width,
+        height,
+        camera: cameras[0].clone(),
+        label_mode,
+        hidden: HashSet::new(),
+    };
+    for (frame, camera) in cameras.into_iter().enumerate() {
+        options.camera = camera;
+        render::render_image(&topology, &layout, &options)
+            .save(output.join(format!("frame_{frame:05}.png")))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,688 @@
+//! A local SQLite store of the scans that have been loaded, so past scans can be reopened by date, compared with
+//! one another, and searched for when a port first appeared.
+//!
+//! Only scanned hosts are stored; routers are rebuilt from their traces when a scan is reopened. Times are
+//! seconds since the Unix epoch and are shown in UTC.
+
+use crate::import::{self, LoadedGraph};
+use crate::model::{
+    OsGuess, OsMatch, SimpleHop, SimpleHost, SimpleHostname, SimplePort, SimpleScript,
+};
+use crate::ndiff::ScanDiff;
+use crate::stream;
+use crate::topology::Topology;
+use domain::base::Dname;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_HISTORY_PATH: &str = "neuromaster-history.sqlite";
+
+/// The source recorded for the built-in scan of scanme.nmap.org, which loads when no files are given.
+pub const SAMPLE_SOURCE: &str = "built-in sample scan of scanme.nmap.org";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    recorded_at INTEGER NOT NULL,
+    -- When the scanned files were last written, if the scan came from files.
+    scanned_at INTEGER,
+    source TEXT NOT NULL,
+    hosts_up INTEGER NOT NULL,
+    hosts_down INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS hosts (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    mac TEXT,
+    main_hostname TEXT,
+    os TEXT,
+    rtt REAL,
+    PRIMARY KEY (scan_id, address)
+);
+CREATE TABLE IF NOT EXISTS hostnames (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT
+);
+CREATE TABLE IF NOT EXISTS ports (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    portid INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    service TEXT,
+    product TEXT,
+    version TEXT
+);
+CREATE INDEX IF NOT EXISTS ports_by_number ON ports (portid, state);
+CREATE TABLE IF NOT EXISTS os_matches (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    name TEXT NOT NULL,
+    accuracy INTEGER
+);
+CREATE TABLE IF NOT EXISTS scripts (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    port TEXT,
+    id TEXT NOT NULL,
+    output TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS hops (
+    scan_id INTEGER NOT NULL REFERENCES scans (id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    ttl INTEGER,
+    hop_address TEXT NOT NULL,
+    hostname TEXT,
+    rtt REAL,
+    loss REAL,
+    jitter REAL
+);
+";
+
+// A scan's time: when it was run if known, otherwise when it was recorded.
+const SCAN_TIME: &str = "COALESCE(scans.scanned_at, scans.recorded_at)";
+
+/// A scan as listed from the history.
+#[derive(Clone, Debug)]
+pub struct ScanRecord {
+    pub id: i64,
+    /// `YYYY-MM-DD HH:MM:SS`, in UTC.
+    pub time: String,
+    pub source: String,
+    pub hosts_up: usize,
+    pub hosts_down: usize,
+}
+
+/// The first scan to find a port open on a host.
+#[derive(Clone, Debug)]
+pub struct FirstSeen {
+    pub addr: IpAddr,
+    pub port: String,
+    pub scan_id: i64,
+    pub time: String,
+}
+
+/// An address range in CIDR notation, such as `10.0.4.0/24`. A bare address stands for itself.
+#[derive(Clone, Copy, Debug)]
+pub struct Network {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl Network {
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        fn masked(bits: u128, width: u32, prefix: u32) -> u128 {
+            bits.checked_shr(width - prefix).unwrap_or(0)
+        }
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                masked(u32::from(network).into(), 32, self.prefix)
+                    == masked(u32::from(addr).into(), 32, self.prefix)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                masked(network.into(), 128, self.prefix) == masked(addr.into(), 128, self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = text.split_once('/').unwrap_or((text, ""));
+        let addr: IpAddr = addr.parse()?;
+        let width = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = if prefix.is_empty() {
+            width
+        } else {
+            prefix.parse()?
+        };
+        if prefix > width {
+            return Err(format!("{text}: the prefix is longer than the address").into());
+        }
+        Ok(Network { addr, prefix })
+    }
+}
+
+/// The newest modification time among the files, for dating scans loaded from them.
+pub fn modified_at(paths: &[PathBuf]) -> Option<i64> {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok()?.modified().ok())
+        .filter_map(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs() as i64)
+        .max()
+}
+
+pub struct History {
+    connection: Connection,
+}
+
+impl History {
+    /// Open the store, creating it if it does not exist yet.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(History { connection })
+    }
+
+    /// Store the scanned hosts of a topology as a new scan, returning its id.
+    pub fn record(
+        &mut self,
+        topology: &Topology,
+        hosts_down: usize,
+        source: &str,
+        scanned_at: Option<i64>,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        let hosts: Vec<&SimpleHost> = topology.hosts().filter(|host| host.scanned).collect();
+        self.record_hosts(&hosts, hosts_down, source, scanned_at)
+    }
+
+    /// Store the hosts as a new scan, returning its id.
+    pub fn record_hosts(
+        &mut self,
+        hosts: &[&SimpleHost],
+        hosts_down: usize,
+        source: &str,
+        scanned_at: Option<i64>,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        let recorded_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO scans (recorded_at, scanned_at, source, hosts_up, hosts_down)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![recorded_at, scanned_at, source, hosts.len(), hosts_down],
+        )?;
+        let scan_id = transaction.last_insert_rowid();
+
+        for host in hosts {
+            let address = host.main_addr.to_string();
+            transaction.execute(
+                "INSERT INTO hosts (scan_id, address, mac, main_hostname, os, rtt)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    scan_id,
+                    address,
+                    host.mac,
+                    host.main_hostname.as_ref().map(|name| name.to_string()),
+                    host.os_guess.as_ref().map(|os| os.name()),
+                    host.rtt
+                ],
+            )?;
+            for hostname in &host.hostnames {
+                transaction.execute(
+                    "INSERT INTO hostnames (scan_id, address, name, kind) VALUES (?1, ?2, ?3, ?4)",
+                    params![scan_id, address, hostname.name, hostname.kind],
+                )?;
+            }
+            for port in &host.ports {
+                transaction.execute(
+                    "INSERT INTO ports (scan_id, address, protocol, portid, state, service, product, version)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        scan_id,
+                        address,
+                        port.protocol,
+                        port.portid,
+                        port.state,
+                        port.service,
+                        port.product,
+                        port.version
+                    ],
+                )?;
+            }
+            for os_match in &host.os_matches {
+                transaction.execute(
+                    "INSERT INTO os_matches (scan_id, address, name, accuracy) VALUES (?1, ?2, ?3, ?4)",
+                    params![scan_id, address, os_match.name, os_match.accuracy],
+                )?;
+            }
+            for script in &host.scripts {
+                transaction.execute(
+                    "INSERT INTO scripts (scan_id, address, port, id, output) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![scan_id, address, script.port, script.id, script.output],
+                )?;
+            }
+            for hop in &host.trace {
+                transaction.execute(
+                    "INSERT INTO hops (scan_id, address, ttl, hop_address, hostname, rtt, loss, jitter)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        scan_id,
+                        address,
+                        hop.ttl,
+                        hop.addr.to_string(),
+                        hop.hostname,
+                        hop.rtt,
+                        hop.loss,
+                        hop.jitter
+                    ],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        Ok(scan_id)
+    }
+
+    /// Every stored scan, oldest first.
+    pub fn scans(&self) -> Result<Vec<ScanRecord>, Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT id, datetime({SCAN_TIME}, 'unixepoch'), source, hosts_up, hosts_down
+             FROM scans ORDER BY {SCAN_TIME}, id"
+        ))?;
+        let scans = statement
+            .query_map([], |row| {
+                Ok(ScanRecord {
+                    id: row.get(0)?,
+                    time: row.get(1)?,
+                    source: row.get(2)?,
+                    hosts_up: row.get(3)?,
+                    hosts_down: row.get(4)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(scans)
+    }
+
+    /// Find a scan by id, or the latest scan up to a date or time such as `2024-05-01` or `2024-05-01 12:00`.
+    pub fn find_scan(&self, spec: &str) -> Result<i64, Box<dyn std::error::Error>> {
+        let found: Option<i64> = match spec.parse::<i64>() {
+            Ok(id) => self
+                .connection
+                .query_row("SELECT id FROM scans WHERE id = ?1", [id], |row| row.get(0))
+                .optional()?,
+            // Scan times are cut to the precision of the date given, so a day includes all of its scans.
+            Err(_) => self
+                .connection
+                .query_row(
+                    &format!(
+                        "SELECT id FROM scans
+                         WHERE substr(datetime({SCAN_TIME}, 'unixepoch'), 1, length(?1)) <= ?1
+                         ORDER BY {SCAN_TIME} DESC, id DESC LIMIT 1"
+                    ),
+                    [spec.replace('T', " ")],
+                    |row| row.get(0),
+                )
+                .optional()?,
+        };
+        Ok(found.ok_or(format!("No scan in the history matches {spec}."))?)
+    }
+
+    /// Rebuild the topology of a stored scan.
+    pub fn load(&self, scan_id: i64) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+        let hosts_down: usize = self.connection.query_row(
+            "SELECT hosts_down FROM scans WHERE id = ?1",
+            [scan_id],
+            |row| row.get(0),
+        )?;
+
+        let mut addresses = Vec::new();
+        let mut hosts = HashMap::new();
+        let mut statement = self.connection.prepare(
+            "SELECT address, mac, main_hostname, os, rtt FROM hosts WHERE scan_id = ?1 ORDER BY rowid",
+        )?;
+        let mut rows = statement.query([scan_id])?;
+        while let Some(row) = rows.next()? {
+            let mut host = SimpleHost::new(row.get::<_, String>(0)?.parse()?);
+            host.scanned = true;
+            host.mac = row.get(1)?;
+            host.main_hostname = row
+                .get::<_, Option<String>>(2)?
+                .and_then(|name| Dname::from_chars(name.chars()).ok());
+            host.os_guess = row
+                .get::<_, Option<String>>(3)?
+                .map(|os| OsGuess::from_string(&os));
+            host.rtt = row.get(4)?;
+            addresses.push(host.main_addr);
+            hosts.insert(host.main_addr, host);
+        }
+
+        self.add_rows(
+            scan_id,
+            "SELECT address, name, kind FROM hostnames WHERE scan_id = ?1 ORDER BY rowid",
+            &mut hosts,
+            |host, row| {
+                host.hostnames.push(SimpleHostname {
+                    name: row.get(1)?,
+                    kind: row.get(2)?,
+                });
+                Ok(())
+            },
+        )?;
+        self.add_rows(
+            scan_id,
+            "SELECT address, protocol, portid, state, service, product, version
+             FROM ports WHERE scan_id = ?1 ORDER BY rowid",
+            &mut hosts,
+            |host, row| {
+                host.ports.push(SimplePort {
+                    protocol: row.get(1)?,
+                    portid: row.get(2)?,
+                    state: row.get(3)?,
+                    service: row.get(4)?,
+                    product: row.get(5)?,
+                    version: row.get(6)?,
+                });
+                Ok(())
+            },
+        )?;
+        self.add_rows(
+            scan_id,
+            "SELECT address, name, accuracy FROM os_matches WHERE scan_id = ?1 ORDER BY rowid",
+            &mut hosts,
+            |host, row| {
+                host.os_matches.push(OsMatch {
+                    name: row.get(1)?,
+                    accuracy: row.get(2)?,
+                });
+                Ok(())
+            },
+        )?;
+        self.add_rows(
+            scan_id,
+            "SELECT address, port, id, output FROM scripts WHERE scan_id = ?1 ORDER BY rowid",
+            &mut hosts,
+            |host, row| {
+                host.scripts.push(SimpleScript {
+                    port: row.get(1)?,
+                    id: row.get(2)?,
+                    output: row.get(3)?,
+                });
+                Ok(())
+            },
+        )?;
+        self.add_rows(
+            scan_id,
+            "SELECT address, ttl, hop_address, hostname, rtt, loss, jitter
+             FROM hops WHERE scan_id = ?1 ORDER BY rowid",
+            &mut hosts,
+            |host, row| {
+                host.trace.push(SimpleHop {
+                    ttl: row.get(1)?,
+                    addr: row.get::<_, String>(2)?.parse()?,
+                    hostname: row.get(3)?,
+                    rtt: row.get(4)?,
+                    loss: row.get(5)?,
+                    jitter: row.get(6)?,
+                });
+                Ok(())
+            },
+        )?;
+
+        let mut topology = Topology::new()?;
+        for addr in addresses {
+            if let Some(host) = hosts.remove(&addr) {
+                topology.add_scanned_host(host);
+            }
+        }
+        topology.attach_orphans();
+        Ok(LoadedGraph {
+            topology,
+            hosts_down,
+        })
+    }
+
+    /// Hand each row of a per-host table to `add` along with its host, which the row's first column names.
+    /// Rows are read in insertion order, which keeps each host's lists in their original order.
+    fn add_rows(
+        &self,
+        scan_id: i64,
+        sql: &str,
+        hosts: &mut HashMap<IpAddr, SimpleHost>,
+        add: impl Fn(&mut SimpleHost, &Row) -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare(sql)?;
+        let mut rows = statement.query([scan_id])?;
+        while let Some(row) = rows.next()? {
+            let addr: IpAddr = row.get::<_, String>(0)?.parse()?;
+            if let Some(host) = hosts.get_mut(&addr) {
+                add(host, row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The changes from one stored scan to another.
+    pub fn diff(&self, old_id: i64, new_id: i64) -> Result<ScanDiff, Box<dyn std::error::Error>> {
+        Ok(ScanDiff::compare(
+            &self.load(old_id)?.topology,
+            &self.load(new_id)?.topology,
+        ))
+    }
+
+    /// When each host was first seen with the port open, optionally only for one protocol and one network,
+    /// earliest first.
+    pub fn first_seen(
+        &self,
+        portid: u16,
+        protocol: Option<&str>,
+        network: Option<Network>,
+    ) -> Result<Vec<FirstSeen>, Box<dyn std::error::Error>> {
+        // With a single min(), SQLite takes the other columns from the row holding the minimum.
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT ports.address, ports.protocol, scans.id, datetime(min({SCAN_TIME}), 'unixepoch')
+             FROM ports JOIN scans ON scans.id = ports.scan_id
+             WHERE ports.portid = ?1 AND ports.state = 'open' AND (?2 IS NULL OR ports.protocol = ?2)
+             GROUP BY ports.address, ports.protocol"
+        ))?;
+        let mut seen = Vec::new();
+        let mut rows = statement.query(params![portid, protocol])?;
+        while let Some(row) = rows.next()? {
+            let addr: IpAddr = row.get::<_, String>(0)?.parse()?;
+            if network.is_some_and(|network| !network.contains(addr)) {
+                continue;
+            }
+            seen.push(FirstSeen {
+                addr,
+                port: format!("{portid}/{}", row.get::<_, String>(1)?),
+                scan_id: row.get(2)?,
+                time: row.get(3)?,
+            });
+        }
+        seen.sort_by(|a, b| a.time.cmp(&b.time).then(a.addr.cmp(&b.addr)));
+        Ok(seen)
+    }
+}
+
+/// Entry point for `neuromaster history`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut path = PathBuf::from(DEFAULT_HISTORY_PATH);
+    let mut command = None;
+    let mut operands = Vec::new();
+    let mut port = None;
+    let mut protocol = None;
+    let mut network = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "--db" => path = PathBuf::from(value(&arg)?),
+            "--port" => port = Some(value(&arg)?.parse::<u16>()?),
+            "--protocol" => protocol = Some(value(&arg)?),
+            "--net" => network = Some(value(&arg)?.parse::<Network>()?),
+            _ if command.is_none() => command = Some(arg),
+            _ => operands.push(arg),
+        }
+    }
+
+    let mut history = History::open(&path)?;
+    match command.as_deref() {
+        Some("record") => {
+            // Without files the built-in sample scan would load, which is not worth keeping.
+            if operands.is_empty() {
+                return Err("record requires the scan files to store".into());
+            }
+            let inputs: Vec<PathBuf> = operands.iter().map(PathBuf::from).collect();
+            let loaded = import::load_inputs(&inputs, stream::report_progress)?;
+            stream::end_progress();
+            let scan_id = history.record(
+                &loaded.topology,
+                loaded.hosts_down,
+                &operands.join(" "),
+                modified_at(&inputs),
+            )?;
+            eprintln!("Recorded scan {scan_id} in {}", path.display());
+        }
+        Some("list") => {
+            for scan in history.scans()? {
+                println!(
+                    "{}\t{}\t{} up, {} down\t{}",
+                    scan.id, scan.time, scan.hosts_up, scan.hosts_down, scan.source
+                );
+            }
+        }
+        Some("first-seen") => {
+            let port = port.ok_or("first-seen requires --port <n>")?;
+            for seen in history.first_seen(port, protocol.as_deref(), network)? {
+                println!(
+                    "{}\t{}\t{} (scan {})",
+                    seen.addr, seen.port, seen.time, seen.scan_id
+                );
+            }
+        }
+        Some("diff") => {
+            let (old, new) = match &operands[..] {
+                [old, new] => (history.find_scan(old)?, history.find_scan(new)?),
+                _ => return Err("diff requires two scans, by id or date".into()),
+            };
+            for (addr, host_diff) in history.diff(old, new)?.hosts {
+                match &host_diff.hostname {
+                    Some(hostname) => println!("{addr} ({hostname})"),
+                    None => println!("{addr}"),
+                }
+                for entry in host_diff.entries() {
+                    println!("  {entry}");
+                }
+            }
+        }
+        _ => {
+            return Err(
+                "usage: neuromaster history [--db <file>] record <files> | list | first-seen --port <n> [--protocol <name>] [--net <cidr>] | diff <scan> <scan>"
+                    .into(),
+            )
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-05-01 00:00:00 UTC.
+    const MAY_DAY: i64 = 1_714_521_600;
+    const HOUR: i64 = 3600;
+
+    fn network(text: &str) -> Network {
+        text.parse().unwrap()
+    }
+
+    fn addr(text: &str) -> IpAddr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn networks_contain_their_addresses() {
+        assert!(network("0.0.0.0/0").contains(addr("203.0.113.9")));
+        assert!(network("10.0.4.0/24").contains(addr("10.0.4.255")));
+        assert!(!network("10.0.4.0/24").contains(addr("10.0.5.0")));
+        assert!(network("10.0.4.7/32").contains(addr("10.0.4.7")));
+        assert!(!network("10.0.4.7/32").contains(addr("10.0.4.8")));
+        // A bare address is a network of one.
+        assert!(network("10.0.4.7").contains(addr("10.0.4.7")));
+        assert!(!network("10.0.4.7").contains(addr("10.0.4.6")));
+    }
+
+    #[test]
+    fn ipv6_networks() {
+        assert!(network("::/0").contains(addr("2001:db8::1")));
+        assert!(network("2001:db8::/32").contains(addr("2001:db8:ffff::1")));
+        assert!(!network("2001:db8::/32").contains(addr("2001:db9::1")));
+        assert!(network("2001:db8::1/128").contains(addr("2001:db8::1")));
+        assert!(!network("2001:db8::1/128").contains(addr("2001:db8::2")));
+        // Families never match each other.
+        assert!(!network("::/0").contains(addr("10.0.0.1")));
+        assert!(!network("0.0.0.0/0").contains(addr("::1")));
+    }
+
+    #[test]
+    fn prefixes_longer_than_the_address_are_refused() {
+        assert!("10.0.0.0/33".parse::<Network>().is_err());
+        assert!("2001:db8::/129".parse::<Network>().is_err());
+        assert!("10.0.0.0/x".parse::<Network>().is_err());
+    }
+
+    /// A history holding three scans, an hour into May Day, at noon and the next day, each finding
+    /// 10.0.4.7 with RDP open.
+    fn history() -> History {
+        let mut history = History::open(Path::new(":memory:")).unwrap();
+        for scanned_at in [MAY_DAY + HOUR, MAY_DAY + 12 * HOUR, MAY_DAY + 24 * HOUR] {
+            let mut topology = Topology::new().unwrap();
+            let mut host = SimpleHost::new(addr("10.0.4.7"));
+            host.scanned = true;
+            host.ports.push(SimplePort {
+                protocol: "tcp".to_string(),
+                portid: 3389,
+                state: "open".to_string(),
+                service: Some("ms-wbt-server".to_string()),
+                product: None,
+                version: None,
+            });
+            topology.add_scanned_host(host);
+            history
+                .record(&topology, 0, "scan.xml", Some(scanned_at))
+                .unwrap();
+        }
+        history
+    }
+
+    #[test]
+    fn find_scans_by_id_or_date() {
+        let history = history();
+        assert_eq!(history.find_scan("2").unwrap(), 2);
+        assert!(history.find_scan("9").is_err());
+        // A day stands for its latest scan.
+        assert_eq!(history.find_scan("2024-05-01").unwrap(), 2);
+        assert_eq!(history.find_scan("2024-05-01 06:00").unwrap(), 1);
+        assert_eq!(history.find_scan("2024-05-01T12:00").unwrap(), 2);
+        assert_eq!(history.find_scan("2024-05-03").unwrap(), 3);
+        assert!(history.find_scan("2024-04-30").is_err());
+    }
+
+    #[test]
+    fn first_seen_is_the_earliest_scan() {
+        let history = history();
+        let seen = history
+            .first_seen(3389, Some("tcp"), Some(network("10.0.4.0/24")))
+            .unwrap();
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].addr, addr("10.0.4.7"));
+        assert_eq!(seen[0].scan_id, 1);
+        assert_eq!(seen[0].time, "2024-05-01 01:00:00");
+        assert!(history
+            .first_seen(3389, None, Some(network("10.0.5.0/24")))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn stored_scans_load_back() {
+        let history = history();
+        let loaded = history.load(1).unwrap();
+        let host = &loaded.topology.graph[loaded.topology.find_host(addr("10.0.4.7")).unwrap()];
+        assert!(host.scanned);
+        assert_eq!(host.ports[0].portid, 3389);
+        assert_eq!(host.ports[0].service.as_deref(), Some("ms-wbt-server"));
+    }
+}
@@ -0,0 +1,808 @@
+//! Loading topologies from sources other than nmap XML.
+//!
+//! nmap's grepable and normal output are parsed in [`crate::nmap_text`], port sweeper results in
+//! [`crate::sweep`] and routes from traceroute and mtr in [`crate::traceroute`].
+//!
+//! # JSON topology schema
+//!
+//! ```json
+//! {
+//!   "hosts": [
+//!     {
+//!       "address": "10.0.4.1",
+//!       "hostnames": ["gw.example.net"],
+//!       "os": "Linux 5.4",
+//!       "rtt": 0.4,
+//!       "ports": [{ "portid": 22, "protocol": "tcp", "state": "open", "service": "ssh" }],
+//!       "trace": [{ "ttl": 1, "address": "10.0.4.1", "hostname": "gw.example.net", "rtt": 0.4, "loss": 0.0, "jitter": 0.1 }]
+//!     }
+//!   ],
+//!   "links": [{ "source": "10.0.4.1", "target": "10.0.4.7", "rtt_delta": 1.3 }]
+//! }
+//! ```
+//!
+//! `hosts` is required and must not be empty. Only `address` is required for a host; every other field
+//! may be omitted. `trace` lists the hops from the scanner to the host, exactly like nmap's `--traceroute`,
+//! and links are derived from it; a hop's `loss` (percent) and `jitter` (milliseconds) are as mtr measures
+//! them. `links` adds further links between hosts by address, for sources that know adjacency but not routes.
+//!
+//! neuromaster's own JSON export, petgraph's serialization of the graph, is recognised by its
+//! `edge_property` field and loaded as well.
+
+use crate::model::{self, OsGuess, SimpleHop, SimpleHost, SimpleHostname, SimpleLink, SimplePort};
+use crate::nmap_text;
+use crate::stream::{HostStream, Progress, ScanEvent};
+use crate::sweep;
+use crate::topology::{self, Topology};
+use crate::traceroute;
+use petgraph::stable_graph::StableGraph;
+use petgraph::Undirected;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use xml::reader::{EventReader, XmlEvent};
+
+/// Input formats neuromaster can build a topology from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    NmapXml,
+    /// nmap's `-oG` output.
+    NmapGrepable,
+    /// nmap's `-oN` output.
+    NmapNormal,
+    GraphMl,
+    Json,
+    MasscanXml,
+    /// masscan's `-oJ` or `-oD` output.
+    MasscanJson,
+    /// masscan's `-oL` output.
+    MasscanList,
+    /// zmap's address list or CSV output.
+    Zmap,
+    /// RustScan's greppable or normal output.
+    RustScan,
+    /// traceroute or paris-traceroute text output.
+    Traceroute,
+    /// `mtr --json` output.
+    Mtr,
+}
+
+impl InputFormat {
+    /// Pick the format from the file extension, falling back to sniffing the content.
+    pub fn detect(path: Option<&Path>, bytes: &[u8]) -> Self {
+        let extension = path
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        // Formats the extension does not settle are told apart by the start of the content. Both XML formats
+        // announce themselves in their root element, near the top of the file, and nmap's text formats by
+        // their first host.
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+        let trimmed = head.trim_start();
+        let first_line = trimmed.lines().next().unwrap_or_default();
+
+        match extension.as_deref() {
+            Some("graphml") => return InputFormat::GraphMl,
+            // Several tools write JSON, so the content decides whose it is.
+            Some("json") => return Self::detect_json(&head).unwrap_or(InputFormat::Json),
+            Some("gnmap") => return InputFormat::NmapGrepable,
+            Some("nmap") => return InputFormat::NmapNormal,
+            Some("csv") => return InputFormat::Zmap,
+            _ => {}
+        }
+
+        if let Some(format) = Self::detect_json(&head) {
+            format
+        } else if head.contains("<graphml") {
+            InputFormat::GraphMl
+        } else if head.contains("scanner=\"masscan\"") {
+            InputFormat::MasscanXml
+        } else if trimmed.starts_with("traceroute") || traceroute::is_hop_line(first_line) {
+            InputFormat::Traceroute
+        } else if head.contains("<nmaprun") {
+            InputFormat::NmapXml
+        } else if head.contains("\nHost: ") {
+            InputFormat::NmapGrepable
+        } else if head.contains("Nmap scan report for ") {
+            InputFormat::NmapNormal
+        } else if trimmed.starts_with("#masscan") || trimmed.starts_with("open ") {
+            InputFormat::MasscanList
+        } else if head.contains(" -> [") || trimmed.starts_with("Open ") {
+            InputFormat::RustScan
+        } else if first_line.starts_with("saddr")
+            || first_line
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .parse::<IpAddr>()
+                .is_ok()
+        {
+            InputFormat::Zmap
+        } else {
+            InputFormat::NmapXml
+        }
+    }
+
+    /// Tell the JSON formats apart by the keys near the top of the document.
+    fn detect_json(head: &str) -> Option<Self> {
+        let trimmed = head.trim_start();
+        if trimmed.starts_with('{') && head.contains("\"report\"") && head.contains("\"mtr\"") {
+            Some(InputFormat::Mtr)
+        } else if trimmed.starts_with(['[', '{'])
+            && head.contains("\"ip\"")
+            && !head.contains("\"hosts\"")
+        {
+            Some(InputFormat::MasscanJson)
+        } else if trimmed.starts_with('{') {
+            Some(InputFormat::Json)
+        } else {
+            None
+        }
+    }
+}
+
+/// A topology, along with what was left out of it while loading.
+pub struct LoadedGraph {
+    pub topology: Topology,
+    /// Hosts the source reported as down. Only nmap scans record these.
+    pub hosts_down: usize,
+}
+
+/// An opened input, with its format detected from the path and the start of its content.
+pub struct Input {
+    pub reader: Box<dyn BufRead + Send>,
+    pub format: InputFormat,
+    /// Unknown when the size cannot be read up front.
+    pub total_bytes: Option<u64>,
+}
+
+/// Open the input file, or the built-in scan of scanme.nmap.org if none is given, without reading it all.
+pub fn open_input(path: Option<&Path>) -> Result<Input, Box<dyn std::error::Error>> {
+    let (mut reader, total_bytes): (Box<dyn BufRead + Send>, Option<u64>) = match path {
+        Some(path) => {
+            let file = File::open(path)?;
+            let total_bytes = file.metadata().ok().map(|metadata| metadata.len());
+            (Box::new(BufReader::new(file)), total_bytes)
+        }
+        None => {
+            let bytes: &'static [u8] = include_bytes!("../assets/scan.xml");
+            (Box::new(bytes), Some(bytes.len() as u64))
+        }
+    };
+    let format = InputFormat::detect(path, reader.fill_buf()?);
+    Ok(Input {
+        reader,
+        format,
+        total_bytes,
+    })
+}
+
+/// Load the topology for the input, streaming nmap XML host by host and reporting progress as it goes.
+pub fn load_input(
+    path: Option<&Path>,
+    mut on_progress: impl FnMut(&Progress),
+) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+    let mut input = open_input(path)?;
+    match input.format {
+        InputFormat::NmapXml => {
+            let mut topology = Topology::new()?;
+            let mut stream = HostStream::new(input.reader, input.total_bytes);
+            while let Some(event) = stream.next() {
+                if let ScanEvent::Host(host) = event? {
+                    topology.add_scanned_host(host);
+                }
+                on_progress(&stream.progress());
+            }
+            Ok(LoadedGraph {
+                topology,
+                hosts_down: stream.progress().hosts_down,
+            })
+        }
+        format => {
+            let mut bytes = Vec::new();
+            input.reader.read_to_end(&mut bytes)?;
+            load_graph(&bytes, format)
+        }
+    }
+}
+
+/// Load every input and merge them in order, so later files add to and update the hosts of earlier ones,
+/// for instance nmap results deepening a masscan sweep. With no inputs, the built-in scan is loaded.
+/// Hosts left without any links are attached to the scanner.
+pub fn load_inputs(
+    paths: &[PathBuf],
+    mut on_progress: impl FnMut(&Progress),
+) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+    let mut loaded = load_input(paths.first().map(PathBuf::as_path), &mut on_progress)?;
+    for path in paths.iter().skip(1) {
+        let other = load_input(Some(path), &mut on_progress)?;
+        loaded.topology.merge(&other.topology)?;
+        loaded.hosts_down += other.hosts_down;
+    }
+    loaded.topology.attach_orphans();
+    Ok(loaded)
+}
+
+/// Build the topology for the input, in the given format.
+pub fn load_graph(
+    bytes: &[u8],
+    format: InputFormat,
+) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+    match format {
+        InputFormat::NmapXml => {
+            let full_parse = rust_nmap::parse_nmap_xml_bytes(bytes)?;
+            let hosts_down = model::count_hosts_down(&full_parse);
+            Ok(LoadedGraph {
+                topology: topology::build_topology(full_parse)?,
+                hosts_down,
+            })
+        }
+        InputFormat::NmapGrepable => nmap_text::import_grepable(bytes),
+        InputFormat::NmapNormal => nmap_text::import_normal(bytes),
+        InputFormat::MasscanXml => Ok(LoadedGraph {
+            topology: sweep::import_masscan_xml(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::MasscanJson => Ok(LoadedGraph {
+            topology: sweep::import_masscan_json(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::MasscanList => Ok(LoadedGraph {
+            topology: sweep::import_masscan_list(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::Zmap => Ok(LoadedGraph {
+            topology: sweep::import_zmap(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::RustScan => Ok(LoadedGraph {
+            topology: sweep::import_rustscan(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::Traceroute => Ok(LoadedGraph {
+            topology: traceroute::import_traceroute(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::Mtr => Ok(LoadedGraph {
+            topology: traceroute::import_mtr(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::GraphMl => Ok(LoadedGraph {
+            topology: import_graphml(bytes)?,
+            hosts_down: 0,
+        }),
+        InputFormat::Json => Ok(LoadedGraph {
+            topology: import_json(bytes)?,
+            hosts_down: 0,
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonTopology {
+    hosts: Vec<JsonHost>,
+    #[serde(default)]
+    links: Vec<JsonLink>,
+}
+
+#[derive(Deserialize)]
+struct JsonHost {
+    address: IpAddr,
+    /// Only present in exported graphs, whose traceroute hops are hosts of their own.
+    #[serde(default = "default_scanned")]
+    scanned: bool,
+    #[serde(default)]
+    hostnames: Vec<String>,
+    os: Option<String>,
+    rtt: Option<f32>,
+    #[serde(default)]
+    ports: Vec<JsonPort>,
+    #[serde(default)]
+    trace: Vec<JsonHop>,
+}
+
+#[derive(Deserialize)]
+struct JsonPort {
+    portid: u16,
+    #[serde(default = "default_protocol")]
+    protocol: String,
+    #[serde(default = "default_state")]
+    state: String,
+    service: Option<String>,
+    product: Option<String>,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JsonHop {
+    ttl: Option<u32>,
+    address: IpAddr,
+    hostname: Option<String>,
+    rtt: Option<f32>,
+    loss: Option<f32>,
+    jitter: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct JsonLink {
+    source: IpAddr,
+    target: IpAddr,
+    #[serde(default)]
+    traces: Vec<IpAddr>,
+    rtt_delta: Option<f32>,
+}
+
+/// A link in an exported graph, whose endpoints are given by node index.
+#[derive(Deserialize)]
+struct JsonEdge {
+    #[serde(default)]
+    traces: Vec<IpAddr>,
+    rtt_delta: Option<f32>,
+}
+
+fn default_scanned() -> bool {
+    true
+}
+
+pub(crate) fn default_protocol() -> String {
+    "tcp".to_string()
+}
+
+pub(crate) fn default_state() -> String {
+    "open".to_string()
+}
+
+impl JsonHost {
+    fn into_host(self) -> SimpleHost {
+        let mut host = SimpleHost::new(self.address);
+        host.scanned = self.scanned;
+        host.hostnames = self
+            .hostnames
+            .into_iter()
+            .map(|name| SimpleHostname { name, kind: None })
+            .collect();
+        host.main_hostname = host
+            .hostnames
+            .first()
+            .and_then(|hostname| domain::base::Dname::from_chars(hostname.name.chars()).ok());
+        host.os_guess = self.os.as_ref().map(OsGuess::from_string);
+        host.rtt = self.rtt;
+        host.ports = self
+            .ports
+            .into_iter()
+            .map(|port| SimplePort {
+                protocol: port.protocol,
+                portid: port.portid,
+                state: port.state,
+                service: port.service,
+                product: port.product,
+                version: port.version,
+            })
+            .collect();
+        host.trace = self
+            .trace
+            .into_iter()
+            .map(|hop| SimpleHop {
+                ttl: hop.ttl,
+                addr: hop.address,
+                hostname: hop.hostname,
+                rtt: hop.rtt,
+                loss: hop.loss,
+                jitter: hop.jitter,
+            })
+            .collect();
+        host
+    }
+}
+
+/// Build the topology from a document in the JSON topology schema, or from neuromaster's JSON export.
+pub fn import_json(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let document: serde_json::Value = serde_json::from_slice(bytes)?;
+    if document.get("edge_property").is_some() {
+        return import_exported_json(document);
+    }
+    let document: JsonTopology =
+        serde_json::from_value(document).map_err(|e| format!("Not a JSON topology: {e}"))?;
+    if document.hosts.is_empty() {
+        return Err("JSON topology has no hosts.".into());
+    }
+    let mut topology = Topology::new()?;
+
+    for json_host in document.hosts {
+        topology.add_scanned_host(json_host.into_host());
+    }
+
+    for link in document.links {
+        topology.add_link(
+            link.source,
+            link.target,
+            SimpleLink {
+                traces: link.traces,
+                rtt_delta: link.rtt_delta,
+            },
+        )?;
+    }
+
+    Ok(topology)
+}
+
+/// Rebuild the topology from petgraph's serialization of an exported graph. The scanner is exported like
+/// any other host, and merges into the new topology's own.
+fn import_exported_json(
+    document: serde_json::Value,
+) -> Result<Topology, Box<dyn std::error::Error>> {
+    let mut graph: StableGraph<JsonHost, JsonEdge, Undirected> =
+        serde_json::from_value(document)
+            .map_err(|e| format!("Not a neuromaster graph export: {e}"))?;
+    let links: Vec<(IpAddr, IpAddr, SimpleLink)> = graph
+        .edge_indices()
+        .filter_map(|edge_index| {
+            let (source, target) = graph.edge_endpoints(edge_index)?;
+            let edge = &graph[edge_index];
+            let link = SimpleLink {
+                traces: edge.traces.clone(),
+                rtt_delta: edge.rtt_delta,
+            };
+            Some((graph[source].address, graph[target].address, link))
+        })
+        .collect();
+    let mut topology = Topology::new()?;
+
+    let node_indices: Vec<_> = graph.node_indices().collect();
+    for node_index in node_indices {
+        topology.add_host(graph.remove_node(node_index).unwrap().into_host());
+    }
+    if topology.graph.node_count() < 2 {
+        return Err("Graph export has no hosts besides the scanner.".into());
+    }
+    for (source, target, link) in links {
+        topology.add_link(source, target, link)?;
+    }
+
+    Ok(topology)
+}
+
+/// A GraphML node or edge with its data values, keyed by attribute name.
+struct GraphMlElement {
+    id: String,
+    endpoints: Option<(String, String)>,
+    data: HashMap<String, String>,
+}
+
+/// Build the topology from GraphML, such as neuromaster's own export.
+/// Hosts are identified by an `address`, `ip` or `ipaddr` attribute, or failing that by a node id or
+/// `label` that parses as an address. `hostnames`, `os`, `rtt` and `ports` are read when present.
+pub fn import_graphml(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let mut key_names = HashMap::<String, String>::new();
+    let mut nodes = Vec::<GraphMlElement>::new();
+    let mut edges = Vec::<GraphMlElement>::new();
+
+    let mut current: Option<GraphMlElement> = None;
+    let mut data_key: Option<String> = None;
+    let mut text = String::new();
+
+    for event in EventReader::new(bytes) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attribute = |wanted: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == wanted)
+                        .map(|attribute| attribute.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "key" => {
+                        if let (Some(id), Some(attr_name)) =
+                            (attribute("id"), attribute("attr.name"))
+                        {
+                            key_names.insert(id, attr_name);
+                        }
+                    }
+                    "node" => {
+                        current = Some(GraphMlElement {
+                            id: attribute("id").ok_or("GraphML node without an id.")?,
+                            endpoints: None,
+                            data: HashMap::new(),
+                        });
+                    }
+                    "edge" => {
+                        let source = attribute("source").ok_or("GraphML edge without a source.")?;
+                        let target = attribute("target").ok_or("GraphML edge without a target.")?;
+                        current = Some(GraphMlElement {
+                            id: attribute("id").unwrap_or_default(),
+                            endpoints: Some((source, target)),
+                            data: HashMap::new(),
+                        });
+                    }
+                    "data" => {
+                        data_key = attribute("key");
+                        text.clear();
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(characters) | XmlEvent::CData(characters) => {
+                if data_key.is_some() {
+                    text.push_str(&characters);
+                }
+            }
+            XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                "data" => {
+                    if let (Some(key), Some(element)) = (data_key.take(), current.as_mut()) {
+                        let attr_name = key_names.get(&key).cloned().unwrap_or(key);
+                        element.data.insert(attr_name, text.trim().to_string());
+                    }
+                }
+                "node" => nodes.extend(current.take()),
+                "edge" => edges.extend(current.take()),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    let mut topology = Topology::new()?;
+    let mut addresses = HashMap::<String, IpAddr>::new();
+
+    for node in nodes {
+        let addr = ["address", "ip", "ipaddr"]
+            .iter()
+            .filter_map(|key| node.data.get(*key))
+            .chain([&node.id])
+            .chain(node.data.get("label"))
+            .find_map(|candidate| candidate.parse::<IpAddr>().ok())
+            .ok_or(format!("GraphML node {} has no address.", node.id))?;
+
+        let mut host = SimpleHost::new(addr);
+        // Sources other than neuromaster's export list their hosts, not the routes to them.
+        host.scanned = node
+            .data
+            .get("scanned")
+            .map_or(true, |scanned| scanned != "false");
+        let hostnames: Vec<String> = match node.data.get("hostnames") {
+            Some(hostnames) => hostnames
+                .split(';')
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => ["hostname", "name", "label"]
+                .iter()
+                .filter_map(|key| node.data.get(*key))
+                .filter(|name| name.parse::<IpAddr>().is_err())
+                .take(1)
+                .cloned()
+                .collect(),
+        };
+        host.main_hostname = hostnames
+            .first()
+            .and_then(|name| domain::base::Dname::from_chars(name.chars()).ok());
+        host.hostnames = hostnames
+            .into_iter()
+            .map(|name| SimpleHostname { name, kind: None })
+            .collect();
+        host.os_guess = node.data.get("os").map(OsGuess::from_string);
+        host.rtt = node.data.get("rtt").and_then(|rtt| rtt.parse().ok());
+        host.ports = node
+            .data
+            .get("ports")
+            .map(|ports| parse_port_summary(ports))
+            .unwrap_or_default();
+
+        topology.add_host(host);
+        addresses.insert(node.id, addr);
+    }
+
+    for edge in edges {
+        let (source, target) = edge.endpoints.unwrap();
+        let link = SimpleLink {
+            traces: edge
+                .data
+                .get("traces")
+                .map(|traces| {
+                    traces
+                        .split(';')
+                        .filter_map(|trace| trace.parse().ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            rtt_delta: edge
+                .data
+                .get("rtt_delta")
+                .and_then(|rtt_delta| rtt_delta.parse().ok()),
+        };
+        let source = *addresses
+            .get(&source)
+            .ok_or(format!("GraphML edge {} has an unknown source.", edge.id))?;
+        let target = *addresses
+            .get(&target)
+            .ok_or(format!("GraphML edge {} has an unknown target.", edge.id))?;
+        topology.add_link(source, target, link)?;
+    }
+
+    Ok(topology)
+}
+
+/// Parse the open port summary written by the exporters, e.g. `22/tcp ssh, 80/tcp http`.
+fn parse_port_summary(summary: &str) -> Vec<SimplePort> {
+    summary
+        .split(", ")
+        .filter_map(|entry| {
+            let mut words = entry.split_whitespace();
+            let (portid, protocol) = words.next()?.split_once('/')?;
+            Some(SimplePort {
+                protocol: protocol.to_string(),
+                portid: portid.parse().ok()?,
+                state: "open".to_string(),
+                service: words.next().map(str::to_string),
+                product: None,
+                version: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::{self, ExportFormat};
+    use crate::layout::Layout;
+
+    const TOPOLOGY: &str = r#"{
+        "hosts": [
+            {
+                "address": "10.0.4.7",
+                "hostnames": ["web.example.net"],
+                "os": "Linux 5.4",
+                "ports": [{ "portid": 443, "service": "https" }],
+                "trace": [
+                    { "ttl": 1, "address": "10.0.4.1", "rtt": 0.4 },
+                    { "ttl": 2, "address": "10.0.4.7", "rtt": 1.7 }
+                ]
+            },
+            { "address": "10.0.4.9" }
+        ],
+        "links": [{ "source": "10.0.4.7", "target": "10.0.4.9" }]
+    }"#;
+
+    fn host<'a>(topology: &'a Topology, addr: &str) -> &'a SimpleHost {
+        &topology.graph[topology.find_host(addr.parse().unwrap()).unwrap()]
+    }
+
+    /// Every link as a sorted pair of addresses, so topologies built in different orders compare equal.
+    fn links(topology: &Topology) -> Vec<(IpAddr, IpAddr)> {
+        let mut links: Vec<(IpAddr, IpAddr)> = topology
+            .link_indices()
+            .map(|link| {
+                let (source, target) = topology.graph.edge_endpoints(link).unwrap();
+                let (source, target) = (
+                    topology.graph[source].main_addr,
+                    topology.graph[target].main_addr,
+                );
+                (source.min(target), source.max(target))
+            })
+            .collect();
+        links.sort();
+        links
+    }
+
+    #[test]
+    fn json_topologies_link_hosts_along_their_traces() {
+        let topology = import_json(TOPOLOGY.as_bytes()).unwrap();
+        assert_eq!(topology.graph.node_count(), 4);
+        assert!(!host(&topology, "10.0.4.1").scanned);
+
+        let web = host(&topology, "10.0.4.7");
+        assert!(web.scanned);
+        assert_eq!(web.hostnames[0].name, "web.example.net");
+        assert_eq!(web.ports[0].portid, 443);
+        assert_eq!(web.ports[0].protocol, "tcp");
+        assert_eq!(web.ports[0].state, "open");
+
+        let addr = |addr: &str| addr.parse::<IpAddr>().unwrap();
+        assert_eq!(
+            links(&topology),
+            [
+                (addr("10.0.4.1"), addr("10.0.4.7")),
+                (addr("10.0.4.1"), addr("127.0.0.1")),
+                (addr("10.0.4.7"), addr("10.0.4.9")),
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelated_json_is_not_a_topology() {
+        for document in [
+            r#"{ "name": "neuromaster", "version": 1 }"#,
+            r#"{ "hosts": [] }"#,
+        ] {
+            assert_eq!(
+                InputFormat::detect(Some(Path::new("other.json")), document.as_bytes()),
+                InputFormat::Json
+            );
+            assert!(import_json(document.as_bytes()).is_err());
+        }
+        assert!(import_json(
+            br#"{ "nodes": [], "node_holes": [], "edge_property": "undirected", "edges": [] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn graphml_from_other_tools_is_read_by_address() {
+        let document = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="d0" for="node" attr.name="label" attr.type="string"/>
+  <graph edgedefault="undirected">
+    <node id="10.0.0.1"><data key="d0">gw.example.net</data></node>
+    <node id="b"><data key="d0">10.0.0.2</data></node>
+    <edge source="10.0.0.1" target="b"/>
+  </graph>
+</graphml>
+"#;
+        assert_eq!(
+            InputFormat::detect(None, document.as_bytes()),
+            InputFormat::GraphMl
+        );
+        let topology = import_graphml(document.as_bytes()).unwrap();
+        let gateway = host(&topology, "10.0.0.1");
+        assert!(gateway.scanned);
+        assert_eq!(gateway.hostnames[0].name, "gw.example.net");
+        assert!(host(&topology, "10.0.0.2").hostnames.is_empty());
+        assert_eq!(links(&topology).len(), 1);
+
+        let unaddressed = document.replace("10.0.0.2", "printer");
+        assert!(import_graphml(unaddressed.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn exports_import_back_into_the_same_topology() {
+        let topology = import_json(TOPOLOGY.as_bytes()).unwrap();
+        let layout = Layout::new(&topology);
+        for format in [ExportFormat::GraphMl, ExportFormat::Json] {
+            let mut bytes = Vec::new();
+            export::export_graph(&topology, &layout, format, &mut bytes).unwrap();
+            let path = match format {
+                ExportFormat::GraphMl => "topology.graphml",
+                _ => "topology.json",
+            };
+            let detected = InputFormat::detect(Some(Path::new(path)), &bytes);
+            let imported = match detected {
+                InputFormat::GraphMl => import_graphml(&bytes),
+                InputFormat::Json => import_json(&bytes),
+                other => panic!("{format:?} export detected as {other:?}"),
+            }
+            .unwrap();
+
+            assert_eq!(imported.graph.node_count(), topology.graph.node_count());
+            assert_eq!(links(&imported), links(&topology));
+            for original in topology.hosts() {
+                let host = host(&imported, &original.main_addr.to_string());
+                assert_eq!(host.scanned, original.scanned, "{format:?}");
+                assert_eq!(host.hostnames.len(), original.hostnames.len());
+                let ports = |host: &SimpleHost| -> Vec<(u16, String)> {
+                    host.ports
+                        .iter()
+                        .map(|port| (port.portid, port.protocol.clone()))
+                        .collect()
+                };
+                assert_eq!(ports(host), ports(original), "{format:?}");
+            }
+            let web = host(&imported, "10.0.4.7");
+            assert_eq!(web.os_guess.as_ref().unwrap().name(), "Linux 5.4");
+            let link = imported
+                .graph
+                .find_edge(
+                    imported.find_host("10.0.4.1".parse().unwrap()).unwrap(),
+                    imported.find_host("10.0.4.7".parse().unwrap()).unwrap(),
+                )
+                .unwrap();
+            assert_eq!(imported.graph[link].traces.len(), 1, "{format:?}");
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! A flat table of hosts for spreadsheets and documents: address, MAC, names, OS, open ports, distance and
+//! round-trip time, one row per host, written as CSV or Markdown.
+
+use crate::import;
+use crate::model::SimpleHost;
+use crate::stats;
+use crate::stream;
+use crate::subset::Criteria;
+use crate::topology::{HostIndex, Topology};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) const COLUMNS: [&str; 9] = [
+    "address",
+    "mac",
+    "hostnames",
+    "os_family",
+    "os_match",
+    "open_ports",
+    "hops",
+    "rtt_ms",
+    "scanned",
+];
+
+/// Table formats the inventory can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InventoryFormat {
+    Csv,
+    Markdown,
+}
+
+impl InventoryFormat {
+    /// Guess the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(InventoryFormat::Csv),
+            "md" | "markdown" => Some(InventoryFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// One host as listed in the inventory.
+#[derive(Clone, Debug)]
+pub struct InventoryRow {
+    pub host: SimpleHost,
+    /// Hops from the scanner: the length of the host's traceroute, or for routers their depth in the topology.
+    pub hops: Option<usize>,
+}
+
+impl InventoryRow {
+    /// The host's round-trip time, taken from the last hop of its traceroute when nmap gave none directly.
+    pub fn rtt(&self) -> Option<f32> {
+        self.host
+            .rtt
+            .or_else(|| self.host.trace.last().and_then(|hop| hop.rtt))
+    }
+
+    pub(crate) fn cells(&self) -> [String; 9] {
+        let host = &self.host;
+        let mut hostnames: Vec<&str> = Vec::new();
+        for hostname in &host.hostnames {
+            if !hostnames.contains(&hostname.name.as_str()) {
+                hostnames.push(&hostname.name);
+            }
+        }
+        let open_ports: Vec<String> = host.open_ports().map(|port| port.to_string()).collect();
+        [
+            host.main_addr.to_string(),
+            host.mac.clone().unwrap_or_default(),
+            hostnames.join("; "),
+            host.os_guess
+                .as_ref()
+                .map(|os| os.family().to_string())
+                .unwrap_or_default(),
+            host.os_guess
+                .as_ref()
+                .map(|os| os.name().to_string())
+                .unwrap_or_default(),
+            open_ports.join("; "),
+            self.hops.map(|hops| hops.to_string()).unwrap_or_default(),
+            self.rtt()
+                .map(|rtt| format!("{rtt:.2}"))
+                .unwrap_or_default(),
+            host.scanned.to_string(),
+        ]
+    }
+}
+
+/// List the hosts `keep` accepts, leaving out the scanner, in address order.
+pub fn inventory(
+    topology: &Topology,
+    mut keep: impl FnMut(HostIndex, &SimpleHost) -> bool,
+) -> Vec<InventoryRow> {
+    let depths = stats::breadth_first_depths(&topology.graph, Topology::root());
+    let mut rows: Vec<InventoryRow> = topology
+        .host_indices()
+        .filter(|host_index| *host_index != Topology::root())
+        .filter(|host_index| keep(*host_index, &topology.graph[*host_index]))
+        .map(|host_index| {
+            let host = &topology.graph[host_index];
+            // Scanned hosts without a traceroute hang straight off the scanner, so their depth means nothing.
+            let hops = if !host.trace.is_empty() {
+                Some(host.trace.len())
+            } else if host.scanned {
+                None
+            } else {
+                depths.get(&host_index).copied()
+            };
+            InventoryRow {
+                host: host.clone(),
+                hops,
+            }
+        })
+        .collect();
+    rows.sort_by_key(|row| row.host.main_addr);
+    rows
+}
+
+/// Quote a CSV field if it holds a separator, quote or line break.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Keep a Markdown table cell from splitting into more columns or rows.
+fn escape_markdown(cell: &str) -> String {
+    cell.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Write the rows as a table with a header.
+pub fn write_inventory<W: Write>(
+    rows: &[InventoryRow],
+    format: InventoryFormat,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        InventoryFormat::Csv => {
+            writeln!(writer, "{}", COLUMNS.join(","))?;
+            for row in rows {
+                let cells: Vec<String> = row.cells().iter().map(|cell| escape_csv(cell)).collect();
+                writeln!(writer, "{}", cells.join(","))?;
+            }
+        }
+        InventoryFormat::Markdown => {
+            writeln!(writer, "| {} |", COLUMNS.join(" | "))?;
+            writeln!(writer, "|{}", "---|".repeat(COLUMNS.len()))?;
+            for row in rows {
+                let cells: Vec<String> = row
+                    .cells()
+                    .iter()
+                    .map(|cell| escape_markdown(cell))
+                    .collect();
+                writeln!(writer, "| {} |", cells.join(" | "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the rows to a file, in the format its extension names.
+pub fn save_inventory(
+    rows: &[InventoryRow],
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = InventoryFormat::from_path(path)
+        .ok_or("Unrecognized inventory extension; use .csv or .md.")?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_inventory(rows, format, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Entry point for `neuromaster inventory`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut scanned_only = false;
+    let mut criteria = Criteria::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--scanned" => scanned_only = true,
+            "--host" => criteria.hosts.push(value(&arg)?.parse()?),
+            "--port" => criteria.ports.push(value(&arg)?.parse()?),
+            "--os" => criteria.os.push(value(&arg)?),
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let loaded = import::load_inputs(&inputs, stream::report_progress)?;
+    stream::end_progress();
+    let rows = inventory(&loaded.topology, |_, host| {
+        (host.scanned || !scanned_only) && criteria.matches(host)
+    });
+
+    match output {
+        Some(output) => {
+            save_inventory(&rows, &output)?;
+            eprintln!("Wrote {} hosts to {}", rows.len(), output.display());
+        }
+        // Without an output file the table is printed as Markdown, which reads well in a terminal.
+        None => write_inventory(
+            &rows,
+            InventoryFormat::Markdown,
+            &mut std::io::stdout().lock(),
+        )?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A host behind a gateway, with an OS string holding the characters each format has to escape.
+    fn rows() -> Vec<InventoryRow> {
+        let topology = import::import_json(
+            br#"{
+                "hosts": [
+                    {
+                        "address": "10.0.0.9",
+                        "hostnames": ["db.example.net", "db.example.net", "db"],
+                        "os": "Linux \"lab\", kernel 5.4 | custom\nbuild",
+                        "ports": [{ "portid": 5432, "service": "postgresql" }, { "portid": 22 }],
+                        "trace": [
+                            { "address": "10.0.0.1", "rtt": 0.5 },
+                            { "address": "10.0.0.9", "rtt": 1.25 }
+                        ]
+                    },
+                    { "address": "10.0.0.5" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        inventory(&topology, |_, _| true)
+    }
+
+    fn write(format: InventoryFormat) -> String {
+        let mut bytes = Vec::new();
+        write_inventory(&rows(), format, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn inventories_list_hosts_in_address_order() {
+        let rows = rows();
+        let cells: Vec<[String; 9]> = rows.iter().map(InventoryRow::cells).collect();
+        let addresses: Vec<&str> = cells.iter().map(|cells| cells[0].as_str()).collect();
+        assert_eq!(addresses, ["10.0.0.1", "10.0.0.5", "10.0.0.9"]);
+
+        // The gateway is a router one link from the scanner; 10.0.0.5 has no route to measure.
+        assert_eq!(cells[0][6], "1");
+        assert_eq!(cells[0][8], "false");
+        assert_eq!(cells[1][6], "");
+        assert_eq!(cells[2][2], "db.example.net; db");
+        assert_eq!(cells[2][5], "5432/tcp postgresql; 22/tcp");
+        assert_eq!(cells[2][6], "2");
+        assert_eq!(cells[2][7], "1.25");
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_separators_quotes_and_line_breaks() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+
+        let csv = write(InventoryFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(COLUMNS.join(",").as_str()));
+        assert!(csv.contains(",\"Linux \"\"lab\"\", kernel 5.4 | custom\nbuild\","));
+    }
+
+    #[test]
+    fn markdown_cells_stay_in_their_column_and_row() {
+        assert_eq!(escape_markdown("a|b"), "a\\|b");
+        assert_eq!(escape_markdown("two\r\nlines"), "two  lines");
+
+        let markdown = write(InventoryFormat::Markdown);
+        let lines: Vec<&str> = markdown.lines().collect();
+        // A header, its separator and one line per host.
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "|---|---|---|---|---|---|---|---|---|");
+        assert!(lines[4].contains("| Linux \"lab\", kernel 5.4 \\| custom build |"));
+        for line in lines.iter().filter(|line| !line.starts_with("|-")) {
+            assert_eq!(line.matches(" | ").count(), COLUMNS.len() - 1, "{line}");
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! What node labels say, from a bare address to a user's template, and keeping labels drawn on screen from
+//! overlapping.
+
+use crate::model::SimpleHost;
+
+/// What a node's label shows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LabelMode {
+    Ip,
+    /// The hostname, or the address if the host has none.
+    Hostname,
+    /// The hostname followed by the address in parentheses.
+    Both,
+    /// A template such as `{hostname} ({os})`. Understands `{ip}`, `{hostname}`, `{os}`,
+    /// `{os_name}`, `{rtt}` and `{ports}`; `{hostname}` falls back to the address.
+    Template(String),
+}
+
+impl LabelMode {
+    /// Return the mode after this one, skipping Template if no template was given.
+    pub fn next(&self, template: Option<&str>) -> Self {
+        match (self, template) {
+            (LabelMode::Ip, _) => LabelMode::Hostname,
+            (LabelMode::Hostname, _) => LabelMode::Both,
+            (LabelMode::Both, Some(template)) => LabelMode::Template(template.to_string()),
+            (LabelMode::Both, None) | (LabelMode::Template(_), _) => LabelMode::Ip,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            LabelMode::Ip => "IP",
+            LabelMode::Hostname => "hostname",
+            LabelMode::Both => "hostname and IP",
+            LabelMode::Template(template) => template,
+        }
+    }
+}
+
+/// Render the label for a host in the given mode.
+pub fn format_label(host: &SimpleHost, mode: &LabelMode) -> String {
+    let ip = host.main_addr.to_string();
+    let hostname = host.display_hostname();
+
+    match mode {
+        LabelMode::Ip => ip,
+        LabelMode::Hostname => hostname.unwrap_or(ip),
+        LabelMode::Both => match hostname {
+            Some(hostname) => format!("{hostname} ({ip})"),
+            None => ip,
+        },
+        LabelMode::Template(template) => template
+            .replace("{ip}", &ip)
+            .replace("{hostname}", hostname.as_deref().unwrap_or(&ip))
+            .replace(
+                "{os}",
+                host.os_guess.as_ref().map_or("unknown", |os| os.family()),
+            )
+            .replace(
+                "{os_name}",
+                host.os_guess.as_ref().map_or("unknown", |os| os.name()),
+            )
+            .replace(
+                "{rtt}",
+                &host
+                    .rtt
+                    .map_or("?".to_string(), |rtt| format!("{rtt:.2} ms")),
+            )
+            .replace("{ports}", &host.open_ports().count().to_string()),
+    }
+}
+
+/// Screen-space bookkeeping that keeps labels from overlapping.
+#[derive(Default)]
+pub struct LabelPlacer {
+    placed: Vec<(f32, f32, f32, f32)>,
+}
+
+impl LabelPlacer {
+    /// Reserve the rectangle with top-left corner (x, y) if it overlaps no earlier one.
+    pub fn try_place(&mut self, x: f32, y: f32, width: f32, height: f32) -> bool {
+        let overlaps = self
+            .placed
+            .iter()
+            .any(|(other_x, other_y, other_width, other_height)| {
+                x < other_x + other_width
+                    && *other_x < x + width
+                    && y < other_y + other_height
+                    && *other_y < y + height
+            });
+        if !overlaps {
+            self.placed.push((x, y, width, height));
+        }
+        !overlaps
+    }
+}
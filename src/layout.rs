@@ -0,0 +1,125 @@
+//! Positions for the hosts from a force-directed fdg_sim simulation, kept in step with the topology as hosts
+//! and links come and go.
+
+use crate::topology::{HostIndex, LinkIndex, Topology};
+use fdg_sim::petgraph::graph::{EdgeIndex, NodeIndex};
+use fdg_sim::{ForceGraph, ForceGraphHelper, Simulation, SimulationParameters};
+use std::collections::HashMap;
+
+pub type Position = [f32; 3];
+
+// The viewer advances the layout by this much each frame.
+pub const STEP: f32 = 0.035;
+const MAX_SETTLE_STEPS: usize = 2000;
+// The layout has settled once no host moves further than this in one step.
+const SETTLE_TOLERANCE: f32 = 0.01;
+
+/// Positions for a topology's hosts, computed by an fdg_sim force simulation over a copy of its shape.
+pub struct Layout {
+    simulation: Simulation<HostIndex, ()>,
+    nodes: HashMap<HostIndex, NodeIndex>,
+    /// The layout's edge for each link, with the hosts it joined, since the index of a removed link may be
+    /// reused for another.
+    links: HashMap<LinkIndex, (HostIndex, HostIndex, EdgeIndex)>,
+}
+
+impl Layout {
+    pub fn new(topology: &Topology) -> Self {
+        let simulation = Simulation::from_graph(
+            ForceGraph::default(),
+            SimulationParameters::new(
+                20.0,
+                fdg_sim::Dimensions::Three,
+                fdg_sim::force::fruchterman_reingold(3.0, 0.975),
+            ),
+        );
+        let mut layout = Layout {
+            simulation,
+            nodes: HashMap::new(),
+            links: HashMap::new(),
+        };
+        layout.sync(topology);
+        layout
+    }
+
+    /// Add the hosts and links the topology has gained since the last sync, and drop the links it has lost,
+    /// returning the new hosts.
+    pub fn sync(&mut self, topology: &Topology) -> Vec<HostIndex> {
+        let graph = self.simulation.get_graph_mut();
+        self.links.retain(|link_index, (source, target, edge)| {
+            let kept = topology.graph.edge_endpoints(*link_index) == Some((*source, *target));
+            if !kept {
+                graph.remove_edge(*edge);
+            }
+            kept
+        });
+        let mut added = Vec::new();
+        for host_index in topology.host_indices() {
+            if !self.nodes.contains_key(&host_index) {
+                let name = topology.graph[host_index].main_addr.to_string();
+                self.nodes
+                    .insert(host_index, graph.add_force_node(name, host_index));
+                added.push(host_index);
+            }
+        }
+        for link_index in topology.link_indices() {
+            if self.links.contains_key(&link_index) {
+                continue;
+            }
+            if let Some((source, target)) = topology.graph.edge_endpoints(link_index) {
+                let edge = graph.add_edge(self.nodes[&source], self.nodes[&target], ());
+                self.links.insert(link_index, (source, target, edge));
+            }
+        }
+        added
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.simulation.update(dt);
+    }
+
+    /// Advance the layout until it settles or MAX_SETTLE_STEPS have run.
+    pub fn settle(&mut self) {
+        for _ in 0..MAX_SETTLE_STEPS {
+            let before: Vec<Position> = self.positions().map(|(_, position)| position).collect();
+            self.update(STEP);
+            let movement = before
+                .iter()
+                .zip(self.positions())
+                .map(|(before, (_, after))| {
+                    before
+                        .iter()
+                        .zip(after)
+                        .map(|(a, b)| (b - a).powi(2))
+                        .sum::<f32>()
+                        .sqrt()
+                })
+                .fold(0.0, f32::max);
+            if movement < SETTLE_TOLERANCE {
+                break;
+            }
+        }
+    }
+
+    pub fn position(&self, host_index: HostIndex) -> Position {
+        let location = self.simulation.get_graph()[self.nodes[&host_index]].location;
+        [location.x, location.y, location.z]
+    }
+
+    pub fn set_position(&mut self, host_index: HostIndex, [x, y, z]: Position) {
+        let node = &mut self.simulation.get_graph_mut()[self.nodes[&host_index]];
+        node.location.x = x;
+        node.location.y = y;
+        node.location.z = z;
+    }
+
+    /// Every host's position, in the layout's node order.
+    pub fn positions(&self) -> impl Iterator<Item = (HostIndex, Position)> + '_ {
+        self.simulation.get_graph().node_weights().map(|node| {
+            (
+                node.data,
+                [node.location.x, node.location.y, node.location.z],
+            )
+        })
+    }
+}
@@ -0,0 +1,43 @@
+//! Turn nmap scans into a 3D network topology.
+//!
+//! - [`import`] reads nmap XML, GraphML and JSON into a topology graph rooted at the scanning host.
+//! - [`ndiff`] reads `ndiff -x` comparisons of two scans as a change overlay.
+//! - [`nmap_text`] reads nmap's grepable and normal output.
+//! - [`sweep`] reads masscan, zmap and RustScan results.
+//! - [`traceroute`] reads routes from traceroute, paris-traceroute and mtr.
+//! - [`stream`] reads nmap XML host by host, for large scans and scans still being written.
+//! - [`scan`] runs nmap and streams the hosts it finds.
+//! - [`model`] holds the host, port, hop and link types parsed from scans.
+//! - [`topology`] is the graph of hosts and links, with traceroutes and groups, built from them.
+//! - [`layout`] places a topology's hosts in 3D with a force-directed simulation.
+//! - [`export`] writes the topology as GraphML, GEXF, DOT or JSON.
+//! - [`stats`] summarizes a scan and its topology.
+//! - [`render`] and [`flythrough`] draw the laid-out graph to images without a display.
+//! - [`subset`] writes chosen hosts back out as nmap XML.
+//! - [`inventory`] lists hosts as a CSV or Markdown table.
+//! - [`report`] writes a self-contained HTML report with a picture of the topology.
+//! - [`history`] keeps loaded scans in a local SQLite database for reopening, comparing and searching.
+//! - [`labels`] formats node labels.
+//!
+//! The interactive viewer is the `neuromaster` binary, built with the `viewer` feature.
+
+pub mod export;
+pub mod flythrough;
+mod font;
+pub mod history;
+pub mod import;
+pub mod inventory;
+pub mod labels;
+pub mod layout;
+pub mod model;
+pub mod ndiff;
+pub mod nmap_text;
+pub mod render;
+pub mod report;
+pub mod scan;
+pub mod stats;
+pub mod stream;
+pub mod subset;
+pub mod sweep;
+pub mod topology;
+pub mod traceroute;
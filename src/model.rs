@@ -0,0 +1,523 @@
+use domain::base::Dname;
+use std::net::IpAddr;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimpleHost {
+    pub main_addr: IpAddr,
+    /// Whether the host was a scan target, rather than only seen as a traceroute hop.
+    pub scanned: bool,
+    /// Hardware address, which nmap only learns for hosts on the scanner's own network segment.
+    pub mac: Option<String>,
+    pub main_hostname: Option<Dname<Vec<u8>>>,
+    /// Every hostname nmap reported for the host, main_hostname included.
+    pub hostnames: Vec<SimpleHostname>,
+    pub os_guess: Option<OsGuess>,
+    /// Every OS nmap's fingerprinting matched, best first; os_guess is made from the first.
+    pub os_matches: Vec<OsMatch>,
+    pub rtt: Option<f32>,
+    /// Traceroute from the scanner to this host, in hop order. Empty for intermediate hops.
+    pub trace: Vec<SimpleHop>,
+    pub ports: Vec<SimplePort>,
+    /// Whether the ports come from a port scan, which lists every port it found interesting, rather than
+    /// from a source that only reports some of them.
+    pub ports_scanned: bool,
+    /// Output of the NSE scripts run against the host and its ports.
+    pub scripts: Vec<SimpleScript>,
+}
+
+/// A hostname and where nmap got it from ("user" for names given as targets, "PTR" for reverse DNS).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimpleHostname {
+    pub name: String,
+    pub kind: Option<String>,
+}
+
+/// An OS nmap's fingerprinting matched, and how closely, in percent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OsMatch {
+    pub name: String,
+    pub accuracy: Option<u8>,
+}
+
+/// Output of an NSE script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimpleScript {
+    /// The port the script ran against, as `22/tcp`, or None for host scripts.
+    pub port: Option<String>,
+    pub id: String,
+    pub output: String,
+}
+
+impl SimpleScript {
+    fn from_script(
+        script: &rust_nmap::script,
+        port: Option<String>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            port,
+            id: script.id.clone().ok_or("Failed to find script id.")?,
+            output: script.output.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// A scanned port and the service nmap found on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimplePort {
+    pub protocol: String,
+    pub portid: u16,
+    pub state: String,
+    pub service: Option<String>,
+    pub product: Option<String>,
+    pub version: Option<String>,
+}
+
+impl SimplePort {
+    pub fn from_port(port: &rust_nmap::port) -> Result<Self, Box<dyn std::error::Error>> {
+        let portid = port
+            .portid
+            .as_ref()
+            .ok_or("Failed to find port number.")?
+            .to_string()
+            .parse()?;
+        let state =
+            (|| port.state.as_ref()?.state.as_ref())().ok_or("Failed to find port state.")?;
+        let service = port.service.as_ref();
+
+        Ok(Self {
+            protocol: port.protocol.clone().unwrap_or_else(|| "tcp".to_string()),
+            portid,
+            state: state.to_string(),
+            service: service.and_then(|service| service.name.clone()),
+            product: service.and_then(|service| service.product.clone()),
+            version: service.and_then(|service| service.version.clone()),
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == "open"
+    }
+}
+
+impl std::fmt::Display for SimplePort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}/{}", self.portid, self.protocol)?;
+        if let Some(service) = &self.service {
+            write!(f, " {service}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single traceroute hop, as recorded in a scanned host's trace.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimpleHop {
+    pub ttl: Option<u32>,
+    pub addr: IpAddr,
+    pub hostname: Option<String>,
+    pub rtt: Option<f32>,
+    /// Percentage of probes to the hop that went unanswered. Only mtr measures this.
+    pub loss: Option<f32>,
+    /// Variation in the hop's round-trip time, in milliseconds. Only mtr measures this.
+    pub jitter: Option<f32>,
+}
+
+impl SimpleHop {
+    pub fn from_hop(hop: &rust_nmap::hop) -> Result<Self, Box<dyn std::error::Error>> {
+        let addr = hop
+            .ipaddr
+            .as_ref()
+            .ok_or("Failed to find hop address.")?
+            .parse()?;
+
+        Ok(Self {
+            ttl: hop
+                .ttl
+                .as_ref()
+                .and_then(|ttl| ttl.to_string().parse().ok()),
+            addr,
+            hostname: hop.host.clone(),
+            rtt: hop
+                .rtt
+                .as_ref()
+                .and_then(|rtt| rtt.to_string().parse().ok()),
+            loss: None,
+            jitter: None,
+        })
+    }
+}
+
+/// A link between two adjacent traceroute hops.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimpleLink {
+    /// Addresses of the scanned hosts whose traceroutes traverse this link.
+    pub traces: Vec<IpAddr>,
+    /// Increase in round-trip time across the link, if both ends have a known rtt.
+    pub rtt_delta: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OsGuess {
+    Linux(String),
+    Freebsd(String),
+    Openbsd(String),
+    Other(String),
+}
+
+impl OsGuess {
+    /// Return the OS family the guess was sorted into.
+    pub fn family(&self) -> &str {
+        match self {
+            OsGuess::Linux(_) => "Linux",
+            OsGuess::Freebsd(_) => "FreeBSD",
+            OsGuess::Openbsd(_) => "OpenBSD",
+            OsGuess::Other(_) => "Other",
+        }
+    }
+
+    /// Return the full OS match name the guess was made from.
+    pub fn name(&self) -> &str {
+        match self {
+            OsGuess::Linux(string)
+            | OsGuess::Freebsd(string)
+            | OsGuess::Openbsd(string)
+            | OsGuess::Other(string) => string,
+        }
+    }
+
+    // This works fine if nobody trips LinuxFreeBSDOpenBSD in the nmap database :P
+    pub fn from_string(string: &String) -> OsGuess {
+        if string.contains("Linux") {
+            OsGuess::Linux(string.to_string())
+        } else if string.contains("FreeBSD") {
+            OsGuess::Freebsd(string.to_string())
+        } else if string.contains("OpenBSD") {
+            OsGuess::Openbsd(string.to_string())
+        } else {
+            OsGuess::Other(string.to_string())
+        }
+    }
+}
+
+impl SimpleHost {
+    pub fn from_fullhost(host: &rust_nmap::host) -> Result<Self, Box<dyn std::error::Error>> {
+        let status =
+            (|| host.status.as_ref()?.state.as_ref())().ok_or("Failed to find host status")?;
+
+        if status.as_str() == "down" {
+            return Err("Host is down".into());
+        }
+
+        let address_box =
+            (|| host.address.as_ref()?.first())().ok_or("Failed to find host address.")?;
+
+        let addrtype = address_box
+            .addrtype
+            .as_ref()
+            .ok_or("Failed to find host address type.")?;
+
+        let addr_str = address_box
+            .addr
+            .as_ref()
+            .ok_or("Failed to find host address.")?;
+
+        let addr = match addrtype.as_str() {
+            "ipv4" => IpAddr::V4(addr_str.parse()?),
+            "ipv6" => IpAddr::V6(addr_str.parse()?),
+            other => return Err(format!("Unhandled address type {other}.").into()),
+        };
+
+        let mac = host
+            .address
+            .iter()
+            .flatten()
+            .find(|address| address.addrtype.as_deref() == Some("mac"))
+            .and_then(|address| address.addr.clone());
+
+        let hostname = (|| {
+            host.hostnames
+                .as_ref()?
+                .hostname
+                .as_ref()?
+                .first()?
+                .name
+                .as_ref()
+        })()
+        .map(|hostname| Dname::from_chars(hostname.chars()))
+        .transpose()?;
+
+        let hostnames = (|| host.hostnames.as_ref()?.hostname.as_ref())()
+            .map(|hostnames| {
+                hostnames
+                    .iter()
+                    .filter_map(|hostname| {
+                        Some(SimpleHostname {
+                            name: hostname.name.clone()?,
+                            kind: hostname.r#type.clone(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let os = (|| host.os.as_ref()?.osmatch.as_ref()?.first()?.name.as_ref())()
+            .map(|os| OsGuess::from_string(os));
+
+        let os_matches = (|| host.os.as_ref()?.osmatch.as_ref())()
+            .map(|matches| {
+                matches
+                    .iter()
+                    .filter_map(|os_match| {
+                        Some(OsMatch {
+                            name: os_match.name.clone()?,
+                            accuracy: os_match
+                                .accuracy
+                                .as_ref()
+                                .and_then(|accuracy| accuracy.to_string().parse().ok()),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let trace = (|| host.trace.as_ref()?.hops.as_ref())()
+            .map(|hops| {
+                hops.iter()
+                    .filter_map(|hop| SimpleHop::from_hop(hop).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let ports = (|| host.ports.as_ref()?.port.as_ref())()
+            .map(|ports| {
+                ports
+                    .iter()
+                    .filter_map(|port| SimplePort::from_port(port).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut scripts: Vec<SimpleScript> = (|| host.hostscript.as_ref()?.script.as_ref())()
+            .into_iter()
+            .flatten()
+            .filter_map(|script| SimpleScript::from_script(script, None).ok())
+            .collect();
+        for port in (|| host.ports.as_ref()?.port.as_ref())()
+            .into_iter()
+            .flatten()
+        {
+            let label = SimplePort::from_port(port)
+                .ok()
+                .map(|port| format!("{}/{}", port.portid, port.protocol));
+            scripts.extend(
+                port.script
+                    .iter()
+                    .flatten()
+                    .filter_map(|script| SimpleScript::from_script(script, label.clone()).ok()),
+            );
+        }
+
+        Ok(Self {
+            main_addr: addr,
+            scanned: true,
+            mac,
+            main_hostname: hostname,
+            hostnames,
+            os_guess: os,
+            os_matches,
+            rtt: None,
+            trace,
+            ports_scanned: host.ports.is_some(),
+            ports,
+            scripts,
+        })
+    }
+
+    /// Make a bare host for an address, to be filled in by importers.
+    pub fn new(addr: IpAddr) -> Self {
+        Self {
+            main_addr: addr,
+            scanned: false,
+            mac: None,
+            main_hostname: None,
+            hostnames: Vec::new(),
+            os_guess: None,
+            os_matches: Vec::new(),
+            rtt: None,
+            trace: Vec::new(),
+            ports: Vec::new(),
+            ports_scanned: false,
+            scripts: Vec::new(),
+        }
+    }
+
+    pub fn from_hop(hop: &SimpleHop) -> Self {
+        let mut host = Self::new(hop.addr);
+        host.main_hostname = hop
+            .hostname
+            .as_ref()
+            .and_then(|name| Dname::from_chars(name.chars()).ok());
+        // Traceroute hop names come from reverse DNS.
+        host.hostnames = hop
+            .hostname
+            .iter()
+            .map(|name| SimpleHostname {
+                name: name.to_string(),
+                kind: Some("PTR".to_string()),
+            })
+            .collect();
+        host.rtt = hop.rtt;
+        host
+    }
+
+    pub fn from_strs(addr: &str, hostname: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            main_addr: addr.parse()?,
+            scanned: false,
+            mac: None,
+            main_hostname: Some(Dname::from_chars(hostname.chars())?),
+            hostnames: vec![SimpleHostname {
+                name: hostname.to_string(),
+                kind: None,
+            }],
+            os_guess: None,
+            os_matches: Vec::new(),
+            rtt: None,
+            trace: Vec::new(),
+            ports: Vec::new(),
+            ports_scanned: false,
+            scripts: Vec::new(),
+        })
+    }
+
+    pub fn set_rtt(host: &mut Self, rtt: f32) {
+        host.rtt = Some(rtt);
+    }
+
+    /// Fold another report of the same host into this one. Fields the other report knows take precedence,
+    /// hostnames are combined and ports are merged by protocol and number, so a port scan of a few ports
+    /// deepens a sweep of many without losing the rest.
+    pub fn merge(&mut self, other: SimpleHost) {
+        self.scanned |= other.scanned;
+        if other.mac.is_some() {
+            self.mac = other.mac;
+        }
+        if other.main_hostname.is_some() {
+            self.main_hostname = other.main_hostname;
+        }
+        for hostname in other.hostnames {
+            if !self.hostnames.contains(&hostname) {
+                self.hostnames.push(hostname);
+            }
+        }
+        if other.os_guess.is_some() {
+            self.os_guess = other.os_guess;
+        }
+        if !other.os_matches.is_empty() {
+            self.os_matches = other.os_matches;
+        }
+        if other.rtt.is_some() {
+            self.rtt = other.rtt;
+        }
+        if !other.trace.is_empty() {
+            self.trace = other.trace;
+        }
+        self.ports_scanned |= other.ports_scanned;
+        for port in other.ports {
+            match self
+                .ports
+                .iter_mut()
+                .find(|known| known.portid == port.portid && known.protocol == port.protocol)
+            {
+                Some(known) => *known = port,
+                None => self.ports.push(port),
+            }
+        }
+        for script in other.scripts {
+            if !self.scripts.contains(&script) {
+                self.scripts.push(script);
+            }
+        }
+    }
+
+    /// Return the best name for the host, if it has any.
+    pub fn display_hostname(&self) -> Option<String> {
+        self.main_hostname
+            .as_ref()
+            .map(|name| name.to_string())
+            .or_else(|| self.hostnames.first().map(|hostname| hostname.name.clone()))
+    }
+
+    pub fn open_ports(&self) -> impl Iterator<Item = &SimplePort> {
+        self.ports.iter().filter(|port| port.is_open())
+    }
+}
+
+/// Count the hosts nmap reported as down, which build_topology leaves out.
+pub fn count_hosts_down(scan: &rust_nmap::nmap_run) -> usize {
+    scan.host
+        .iter()
+        .flatten()
+        .filter(|host| is_host_down(host))
+        .count()
+}
+
+pub fn is_host_down(host: &rust_nmap::host) -> bool {
+    (|| host.status.as_ref()?.state.as_ref())().map(|state| state.as_str()) == Some("down")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SimpleHost;
+    use crate::{nmap_text, sweep};
+
+    #[test]
+    fn nmap_results_deepen_a_masscan_sweep() {
+        let sweep = "open tcp 22 10.0.0.1 1700000000\n\
+            open tcp 80 10.0.0.1 1700000000\n\
+            open tcp 8080 10.0.0.1 1700000000\n";
+        let scan = "Nmap scan report for 10.0.0.1\n\
+            PORT   STATE  SERVICE VERSION\n\
+            22/tcp open   ssh     OpenSSH 8.9p1\n\
+            80/tcp closed http\n";
+        let mut topology = sweep::import_masscan_list(sweep.as_bytes()).unwrap();
+        topology
+            .merge(&nmap_text::import_normal(scan.as_bytes()).unwrap().topology)
+            .unwrap();
+
+        let host = &topology.graph[topology.find_host("10.0.0.1".parse().unwrap()).unwrap()];
+        let ports: Vec<(u16, &str)> = host
+            .ports
+            .iter()
+            .map(|port| (port.portid, port.state.as_str()))
+            .collect();
+        // nmap's view of the ports it scanned wins, and the port only masscan saw is kept.
+        assert_eq!(ports, [(22, "open"), (80, "closed"), (8080, "open")]);
+        assert_eq!(host.ports[0].product.as_deref(), Some("OpenSSH 8.9p1"));
+    }
+
+    fn parse_host(host: &str) -> Result<SimpleHost, Box<dyn std::error::Error>> {
+        let scan =
+            rust_nmap::parse_nmap_xml_bytes(format!("<nmaprun>{host}</nmaprun>").as_bytes())?;
+        SimpleHost::from_fullhost(&scan.host.unwrap()[0])
+    }
+
+    #[test]
+    fn unusable_hosts_are_errors() {
+        let host = parse_host(
+            r#"<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/><hostnames><hostname name="gw.example.net" type="PTR"/></hostnames></host>"#,
+        )
+        .unwrap();
+        assert!(host.main_hostname.is_some());
+
+        assert!(parse_host(
+            r#"<host><status state="up"/><address addr="00:11:22:33:44:55" addrtype="mac"/></host>"#
+        )
+        .is_err());
+        let long_label = "a".repeat(64);
+        assert!(parse_host(&format!(
+            r#"<host><status state="up"/><address addr="10.0.0.1" addrtype="ipv4"/><hostnames><hostname name="{long_label}.example.net"/></hostnames></host>"#
+        ))
+        .is_err());
+    }
+}
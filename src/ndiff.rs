@@ -0,0 +1,521 @@
+//! Reading `ndiff -x` output, nmap's own comparison of two scans, as changes to overlay on a topology.
+//! The same changes can be worked out from two loaded topologies, such as scans kept in the history.
+//!
+//! ndiff wraps whatever only the first scan saw in `<a>` and whatever only the second saw in `<b>`;
+//! everything outside them is common to both.
+
+use crate::model::{SimpleHost, SimplePort};
+use crate::topology::Topology;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+/// How a host or port differs between the two scans.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Change {
+    pub fn symbol(self) -> char {
+        match self {
+            Change::Added => '+',
+            Change::Removed => '-',
+            Change::Changed => '~',
+        }
+    }
+
+    /// Compare what each scan saw, if anything.
+    fn between<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> Option<Self> {
+        match (a, b) {
+            (None, Some(_)) => Some(Change::Added),
+            (Some(_), None) => Some(Change::Removed),
+            (Some(a), Some(b)) if a != b => Some(Change::Changed),
+            _ => None,
+        }
+    }
+}
+
+/// A port that differs between the scans, as the second scan saw it unless it was removed.
+#[derive(Clone, Debug)]
+pub struct PortDiff {
+    pub change: Change,
+    pub port: SimplePort,
+    /// The state the first scan saw, for changed ports.
+    pub old_state: Option<String>,
+}
+
+/// Everything that differs for one host.
+#[derive(Clone, Debug)]
+pub struct HostDiff {
+    pub change: Change,
+    pub hostname: Option<String>,
+    /// The host's status in each scan, when it changed.
+    pub status: Option<(String, String)>,
+    pub ports: Vec<PortDiff>,
+}
+
+impl HostDiff {
+    /// One line per difference, as listed in the viewer's side panel.
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+        match self.change {
+            Change::Added => entries.push("+ host added".to_string()),
+            Change::Removed => entries.push("- host removed".to_string()),
+            Change::Changed => {}
+        }
+        if let Some((old, new)) = &self.status {
+            entries.push(format!("~ status {old} -> {new}"));
+        }
+        for diff in &self.ports {
+            let port = &diff.port;
+            let entry = match (&diff.change, &diff.old_state) {
+                (Change::Changed, Some(old_state)) => format!(
+                    "~ {}/{} {old_state} -> {}",
+                    port.portid, port.protocol, port.state
+                ),
+                (change, _) => format!("{} {port} {}", change.symbol(), port.state),
+            };
+            entries.push(entry);
+        }
+        entries
+    }
+
+    /// The host as the newest scan to see it did, with the ports it reported.
+    fn host(&self, addr: IpAddr) -> SimpleHost {
+        let mut host = SimpleHost::new(addr);
+        host.scanned = true;
+        host.ports = self
+            .ports
+            .iter()
+            .filter(|diff| diff.change != Change::Removed || self.change == Change::Removed)
+            .map(|diff| diff.port.clone())
+            .collect();
+        host
+    }
+}
+
+/// The hosts that differ between two scans, by address.
+#[derive(Clone, Debug, Default)]
+pub struct ScanDiff {
+    pub hosts: BTreeMap<IpAddr, HostDiff>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Both,
+    A,
+    B,
+}
+
+/// What each scan saw of one thing.
+struct Observed<T> {
+    a: Option<T>,
+    b: Option<T>,
+}
+
+impl<T: Clone> Observed<T> {
+    fn new() -> Self {
+        Observed { a: None, b: None }
+    }
+
+    fn record(&mut self, side: Side, value: T) {
+        if side != Side::B {
+            self.a = Some(value.clone());
+        }
+        if side != Side::A {
+            self.b = Some(value);
+        }
+    }
+}
+
+/// One `<hostdiff>` as it is read.
+struct PendingHost {
+    addr: Option<IpAddr>,
+    hostname: Option<String>,
+    presence: Observed<()>,
+    status: Observed<String>,
+    ports: BTreeMap<(String, u16), (Observed<String>, SimplePort)>,
+    current_port: Option<(String, u16)>,
+}
+
+impl PendingHost {
+    fn new() -> Self {
+        PendingHost {
+            addr: None,
+            hostname: None,
+            presence: Observed::new(),
+            status: Observed::new(),
+            ports: BTreeMap::new(),
+            current_port: None,
+        }
+    }
+
+    fn finish(self) -> Option<(IpAddr, HostDiff)> {
+        let ports: Vec<PortDiff> = self
+            .ports
+            .into_values()
+            .filter_map(|(state, mut port)| {
+                let change = Change::between(&state.a, &state.b)?;
+                port.state = state.b.clone().or(state.a.clone())?;
+                Some(PortDiff {
+                    change,
+                    port,
+                    old_state: state.a.filter(|_| change == Change::Changed),
+                })
+            })
+            .collect();
+        let status = match (self.status.a, self.status.b) {
+            (Some(a), Some(b)) if a != b => Some((a, b)),
+            _ => None,
+        };
+        let change = Change::between(&self.presence.a, &self.presence.b).unwrap_or(Change::Changed);
+        // ndiff -v lists unchanged hosts too.
+        if change == Change::Changed && status.is_none() && ports.is_empty() {
+            return None;
+        }
+        Some((
+            self.addr?,
+            HostDiff {
+                change,
+                hostname: self.hostname,
+                status,
+                ports,
+            },
+        ))
+    }
+}
+
+impl ScanDiff {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut diff = ScanDiff::default();
+        let mut sides = Vec::new();
+        let mut pending: Option<PendingHost> = None;
+
+        for event in EventReader::new(bytes) {
+            match event? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let attribute = |key: &str| {
+                        attributes
+                            .iter()
+                            .find(|attribute| attribute.name.local_name == key)
+                            .map(|attribute| attribute.value.clone())
+                    };
+                    let side = sides.last().copied().unwrap_or(Side::Both);
+                    match name.local_name.as_str() {
+                        "a" => sides.push(Side::A),
+                        "b" => sides.push(Side::B),
+                        "hostdiff" => pending = Some(PendingHost::new()),
+                        _ => {}
+                    }
+                    let host = match pending.as_mut() {
+                        None => continue,
+                        Some(host) => host,
+                    };
+                    match name.local_name.as_str() {
+                        "host" => host.presence.record(side, ()),
+                        "status" => {
+                            if let Some(state) = attribute("state") {
+                                host.status.record(side, state);
+                            }
+                        }
+                        "address" if attribute("addrtype").as_deref() != Some("mac") => {
+                            host.addr = host
+                                .addr
+                                .or(attribute("addr").and_then(|addr| addr.parse().ok()));
+                        }
+                        "hostname" => host.hostname = host.hostname.take().or(attribute("name")),
+                        "port" => {
+                            host.current_port = None;
+                            if let Some(portid) =
+                                attribute("portid").and_then(|portid| portid.parse().ok())
+                            {
+                                let protocol =
+                                    attribute("protocol").unwrap_or_else(|| "tcp".to_string());
+                                host.ports
+                                    .entry((protocol.clone(), portid))
+                                    .or_insert_with(|| {
+                                        (
+                                            Observed::new(),
+                                            SimplePort {
+                                                protocol: protocol.clone(),
+                                                portid,
+                                                state: String::new(),
+                                                service: None,
+                                                product: None,
+                                                version: None,
+                                            },
+                                        )
+                                    });
+                                host.current_port = Some((protocol, portid));
+                            }
+                        }
+                        "state" | "service" => {
+                            let port = host
+                                .current_port
+                                .as_ref()
+                                .and_then(|key| host.ports.get_mut(key));
+                            if let Some((state, port)) = port {
+                                if name.local_name == "state" {
+                                    if let Some(value) = attribute("state") {
+                                        state.record(side, value);
+                                    }
+                                } else {
+                                    port.service = attribute("name").or(port.service.take());
+                                    port.product = attribute("product").or(port.product.take());
+                                    port.version = attribute("version").or(port.version.take());
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                    "a" | "b" => {
+                        sides.pop();
+                    }
+                    "port" => {
+                        if let Some(host) = pending.as_mut() {
+                            host.current_port = None;
+                        }
+                    }
+                    "hostdiff" => {
+                        if let Some((addr, host_diff)) =
+                            pending.take().and_then(PendingHost::finish)
+                        {
+                            diff.hosts.insert(addr, host_diff);
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        Ok(diff)
+    }
+
+    /// Compare the scanned hosts of two topologies, as ndiff would compare the scans they were loaded from.
+    /// Ports are compared by state.
+    pub fn compare(old: &Topology, new: &Topology) -> Self {
+        fn scanned(topology: &Topology) -> BTreeMap<IpAddr, &SimpleHost> {
+            topology
+                .hosts()
+                .filter(|host| host.scanned)
+                .map(|host| (host.main_addr, host))
+                .collect()
+        }
+        let old_hosts = scanned(old);
+        let new_hosts = scanned(new);
+
+        let addresses: BTreeSet<&IpAddr> = old_hosts.keys().chain(new_hosts.keys()).collect();
+
+        let mut diff = ScanDiff::default();
+        for addr in addresses {
+            let (old_host, new_host) = (old_hosts.get(addr).copied(), new_hosts.get(addr).copied());
+            let change = Change::between(&old_host.map(|_| ()), &new_host.map(|_| ()))
+                .unwrap_or(Change::Changed);
+
+            let mut ports: BTreeMap<(String, u16), Observed<SimplePort>> = BTreeMap::new();
+            for (side, host) in [(Side::A, old_host), (Side::B, new_host)] {
+                for port in host.iter().flat_map(|host| &host.ports) {
+                    ports
+                        .entry((port.protocol.clone(), port.portid))
+                        .or_insert_with(Observed::new)
+                        .record(side, port.clone());
+                }
+            }
+            let ports: Vec<PortDiff> = ports
+                .into_values()
+                .filter_map(|observed| {
+                    let old_state = observed.a.as_ref().map(|port| port.state.clone());
+                    let new_state = observed.b.as_ref().map(|port| port.state.clone());
+                    let change = Change::between(&old_state, &new_state)?;
+                    Some(PortDiff {
+                        change,
+                        port: observed.b.or(observed.a)?,
+                        old_state: old_state.filter(|_| change == Change::Changed),
+                    })
+                })
+                .collect();
+            if change == Change::Changed && ports.is_empty() {
+                continue;
+            }
+
+            diff.hosts.insert(
+                *addr,
+                HostDiff {
+                    change,
+                    hostname: new_host
+                        .or(old_host)
+                        .and_then(|host| host.display_hostname()),
+                    status: None,
+                    ports,
+                },
+            );
+        }
+        diff
+    }
+
+    /// Add the hosts the topology is missing, such as those the newer scan lost, and the ports the diff found.
+    pub fn apply(&self, topology: &mut Topology) {
+        for (addr, host_diff) in &self.hosts {
+            topology.add_scanned_host(host_diff.host(*addr));
+        }
+    }
+
+    pub fn host(&self, addr: IpAddr) -> Option<&HostDiff> {
+        self.hosts.get(&addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NDIFF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmapdiff version="1">
+  <scandiff>
+    <hostdiff>
+      <host>
+        <status state="up"/>
+        <address addr="10.0.0.1" addrtype="ipv4"/>
+        <address addr="00:0C:29:3A:1B:2C" addrtype="mac"/>
+        <hostnames><hostname name="gw.example.net" type="PTR"/></hostnames>
+        <ports>
+          <port protocol="tcp" portid="22">
+            <state state="open"/>
+            <service name="ssh"/>
+          </port>
+          <port protocol="tcp" portid="80">
+            <a><state state="open"/></a>
+            <b><state state="closed"/></b>
+            <service name="http"/>
+          </port>
+          <a>
+            <port protocol="tcp" portid="23">
+              <state state="open"/>
+              <service name="telnet"/>
+            </port>
+          </a>
+          <b>
+            <port protocol="tcp" portid="443">
+              <state state="open"/>
+              <service name="https"/>
+            </port>
+          </b>
+        </ports>
+      </host>
+    </hostdiff>
+    <hostdiff>
+      <b>
+        <host>
+          <status state="up"/>
+          <address addr="10.0.0.7" addrtype="ipv4"/>
+        </host>
+      </b>
+    </hostdiff>
+    <hostdiff>
+      <host>
+        <status state="up"/>
+        <address addr="10.0.0.9" addrtype="ipv4"/>
+      </host>
+    </hostdiff>
+  </scandiff>
+</nmapdiff>
+"#;
+
+    fn port(portid: u16, state: &str) -> SimplePort {
+        SimplePort {
+            protocol: "tcp".to_string(),
+            portid,
+            state: state.to_string(),
+            service: None,
+            product: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn port_changes_inside_a_and_b() {
+        let diff = ScanDiff::parse(NDIFF.as_bytes()).unwrap();
+        // The unchanged host ndiff -v lists is left out.
+        assert_eq!(diff.hosts.len(), 2);
+
+        let gateway = diff.host("10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(gateway.change, Change::Changed);
+        assert_eq!(gateway.hostname.as_deref(), Some("gw.example.net"));
+        let ports: Vec<(u16, Change, &str, Option<&str>)> = gateway
+            .ports
+            .iter()
+            .map(|diff| {
+                (
+                    diff.port.portid,
+                    diff.change,
+                    diff.port.state.as_str(),
+                    diff.old_state.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ports,
+            [
+                (23, Change::Removed, "open", None),
+                (80, Change::Changed, "closed", Some("open")),
+                (443, Change::Added, "open", None),
+            ]
+        );
+        assert!(gateway
+            .entries()
+            .contains(&"~ 80/tcp open -> closed".to_string()));
+
+        let added = diff.host("10.0.0.7".parse().unwrap()).unwrap();
+        assert_eq!(added.change, Change::Added);
+        assert_eq!(added.entries()[0], "+ host added");
+    }
+
+    #[test]
+    fn compare_topologies_by_port_state() {
+        fn add(topology: &mut Topology, addr: &str, ports: Vec<SimplePort>) {
+            let mut host = SimpleHost::new(addr.parse().unwrap());
+            host.scanned = true;
+            host.ports = ports;
+            topology.add_scanned_host(host);
+        }
+        let mut old = Topology::new().unwrap();
+        add(
+            &mut old,
+            "10.0.0.1",
+            vec![port(22, "open"), port(80, "open")],
+        );
+        add(&mut old, "10.0.0.2", Vec::new());
+        let mut new = Topology::new().unwrap();
+        add(
+            &mut new,
+            "10.0.0.1",
+            vec![port(22, "open"), port(80, "filtered")],
+        );
+
+        let diff = ScanDiff::compare(&old, &new);
+        assert_eq!(diff.hosts.len(), 2);
+        let changed = diff.host("10.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(changed.change, Change::Changed);
+        assert_eq!(changed.ports.len(), 1);
+        assert_eq!(changed.ports[0].port.state, "filtered");
+        assert_eq!(changed.ports[0].old_state.as_deref(), Some("open"));
+        assert_eq!(
+            diff.host("10.0.0.2".parse().unwrap()).unwrap().change,
+            Change::Removed
+        );
+    }
+}
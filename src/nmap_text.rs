@@ -0,0 +1,380 @@
+//! Loading nmap's grepable (`-oG`) and normal (`-oN`) output, for archived scans that were not saved as XML.
+//!
+//! Both formats carry less than XML: grepable output has no traceroutes, and neither splits a service's
+//! product from its version, so the whole version string is kept as the product.
+
+use crate::import::LoadedGraph;
+use crate::model::{OsGuess, OsMatch, SimpleHop, SimpleHost, SimpleHostname, SimplePort};
+use crate::topology::Topology;
+use domain::base::Dname;
+use std::net::IpAddr;
+
+/// Where the lines of a normal output host report currently belong.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Summary,
+    Ports,
+    Trace,
+}
+
+/// Build the topology from grepable output, one line per host and kind of result.
+pub fn import_grepable(bytes: &[u8]) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut topology = Topology::new()?;
+    let mut hosts_down = 0;
+
+    for line in text.lines() {
+        let mut fields = line.split('\t');
+        let target = match fields.next().and_then(|field| field.strip_prefix("Host: ")) {
+            None => continue,
+            Some(target) => target,
+        };
+        let (addr, hostname) = target.split_once(' ').unwrap_or((target, ""));
+        let hostname = hostname.trim_start_matches('(').trim_end_matches(')');
+        let mut host = scanned_host(
+            addr.parse()?,
+            Some(hostname).filter(|name| !name.is_empty()),
+        );
+
+        let mut is_down = false;
+        for field in fields {
+            match field.split_once(": ") {
+                Some(("Status", status)) => is_down = status == "Down",
+                Some(("Ports", ports)) => {
+                    host.ports = ports.split(", ").filter_map(parse_grepable_port).collect();
+                    host.ports_scanned = true;
+                }
+                Some(("OS", os)) => host.os_guess = Some(OsGuess::from_string(&os.to_string())),
+                _ => {}
+            }
+        }
+
+        // A host is reported once for its status and again for its ports, and merged by address.
+        if is_down {
+            hosts_down += 1;
+        } else {
+            topology.add_scanned_host(host);
+        }
+    }
+
+    Ok(LoadedGraph {
+        topology,
+        hosts_down,
+    })
+}
+
+/// Parse a port entry such as `22/open/tcp//ssh//OpenSSH 6.6.1p1/`.
+fn parse_grepable_port(entry: &str) -> Option<SimplePort> {
+    let fields: Vec<&str> = entry.split('/').collect();
+    let field = |index: usize| {
+        fields
+            .get(index)
+            .map(|field| field.trim())
+            .filter(|field| !field.is_empty())
+            .map(str::to_string)
+    };
+    Some(SimplePort {
+        portid: field(0)?.parse().ok()?,
+        state: field(1)?,
+        protocol: field(2)?,
+        service: field(4),
+        product: field(6),
+        version: None,
+    })
+}
+
+/// Build the topology from normal output, the report nmap prints to the terminal.
+pub fn import_normal(bytes: &[u8]) -> Result<LoadedGraph, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut topology = Topology::new()?;
+    let mut hosts_down = 0;
+    let mut current: Option<SimpleHost> = None;
+    let mut section = Section::Summary;
+
+    for line in text.lines() {
+        if let Some(target) = line.strip_prefix("Nmap scan report for ") {
+            if let Some(host) = current.take() {
+                topology.add_scanned_host(host);
+            }
+            section = Section::Summary;
+            if target.ends_with("[host down]") {
+                hosts_down += 1;
+            } else {
+                let (addr, hostname) = parse_report_target(target)
+                    .ok_or(format!("Could not parse target {target}."))?;
+                current = Some(scanned_host(addr, hostname));
+            }
+            continue;
+        }
+        let host = match current.as_mut() {
+            None => continue,
+            Some(host) => host,
+        };
+
+        if line.trim().is_empty() {
+            section = Section::Summary;
+        } else if line.starts_with("PORT ") {
+            section = Section::Ports;
+            host.ports_scanned = true;
+        } else if line.starts_with("All ") && line.contains(" scanned ports on ") {
+            // Every port scanned was closed or filtered, so there is no table.
+            host.ports_scanned = true;
+        } else if line.starts_with("HOP ") {
+            section = Section::Trace;
+        } else if let Some((_, name)) = line
+            .strip_prefix("rDNS record for ")
+            .and_then(|record| record.split_once(": "))
+        {
+            host.hostnames.push(SimpleHostname {
+                name: name.to_string(),
+                kind: Some("PTR".to_string()),
+            });
+        } else if let Some(mac) = line.strip_prefix("MAC Address: ") {
+            // The address is followed by the vendor nmap looked it up as, e.g. "00:0C:29:3A:1B:2C (VMware)".
+            host.mac = mac.split_whitespace().next().map(str::to_string);
+        } else if let Some(os) = line.strip_prefix("OS details: ") {
+            host.os_guess = Some(OsGuess::from_string(&os.to_string()));
+        } else if let Some(guesses) = line.strip_prefix("Aggressive OS guesses: ") {
+            // Guesses come best first, each followed by its confidence, e.g. "Linux 3.2 - 4.9 (95%)".
+            host.os_matches = guesses.split("%), ").map(parse_os_guess).collect();
+            if let (None, Some(best)) = (&host.os_guess, host.os_matches.first()) {
+                host.os_guess = Some(OsGuess::from_string(&best.name));
+            }
+        } else if section == Section::Ports {
+            host.ports.extend(parse_normal_port(line));
+        } else if section == Section::Trace {
+            match parse_shared_hops(line) {
+                // The shared hops were listed in full for the earlier host, which has been added already.
+                Some((last_ttl, addr)) => {
+                    if let Some(earlier) = topology.find_host(addr) {
+                        host.trace.extend(
+                            topology.graph[earlier]
+                                .trace
+                                .iter()
+                                .filter(|hop| hop.ttl.is_some_and(|ttl| ttl <= last_ttl))
+                                .cloned(),
+                        );
+                    }
+                }
+                None => host.trace.extend(parse_normal_hop(line)),
+            }
+        }
+    }
+    if let Some(host) = current {
+        topology.add_scanned_host(host);
+    }
+
+    Ok(LoadedGraph {
+        topology,
+        hosts_down,
+    })
+}
+
+/// Parse a port table row such as `22/tcp open ssh OpenSSH 6.6.1p1 Ubuntu`.
+/// Script output below a port starts with `|` and is skipped.
+fn parse_normal_port(line: &str) -> Option<SimplePort> {
+    let mut words = line.split_whitespace();
+    let (portid, protocol) = words.next()?.split_once('/')?;
+    let state = words.next()?.to_string();
+    let service = words.next().map(str::to_string);
+    let product = words.collect::<Vec<_>>().join(" ");
+    Some(SimplePort {
+        protocol: protocol.to_string(),
+        portid: portid.parse().ok()?,
+        state,
+        service,
+        product: Some(product).filter(|product| !product.is_empty()),
+        version: None,
+    })
+}
+
+/// Parse one of nmap's aggressive OS guesses, such as `Linux 3.2 - 4.9 (95`, split from the list at `%), `.
+fn parse_os_guess(guess: &str) -> OsMatch {
+    match guess.trim_end_matches("%)").rsplit_once(" (") {
+        Some((name, accuracy)) => OsMatch {
+            name: name.to_string(),
+            accuracy: accuracy.parse().ok(),
+        },
+        None => OsMatch {
+            name: guess.to_string(),
+            accuracy: None,
+        },
+    }
+}
+
+/// Parse a row standing for the hops a trace shares with an earlier host's, such as
+/// `-   Hops 1-3 are the same as for 10.0.0.5` or `-   Hop 1 is the same as for 10.0.0.5`,
+/// into the last shared TTL and the earlier host's address.
+fn parse_shared_hops(line: &str) -> Option<(u32, IpAddr)> {
+    let (hops, target) = line
+        .strip_prefix('-')?
+        .trim_start()
+        .split_once(" the same as for ")?;
+    let range = match hops.strip_prefix("Hops ") {
+        Some(hops) => hops.strip_suffix(" are")?,
+        None => hops.strip_prefix("Hop ")?.strip_suffix(" is")?,
+    };
+    let last_ttl = range.rsplit('-').next()?.parse().ok()?;
+    let (addr, _) = parse_report_target(target)?;
+    Some((last_ttl, addr))
+}
+
+/// Parse a traceroute row such as `3   10.00 ms  router.example.net (10.0.0.1)`.
+/// Rows for unanswered hops have no address and are skipped.
+fn parse_normal_hop(line: &str) -> Option<SimpleHop> {
+    let mut words = line.split_whitespace().peekable();
+    let ttl = words.next()?.parse().ok()?;
+    let rtt = match words.peek().map(|word| word.parse::<f32>()) {
+        Some(Ok(rtt)) => {
+            words.next();
+            if words.peek() == Some(&"ms") {
+                words.next();
+            }
+            Some(rtt)
+        }
+        _ => None,
+    };
+    let target = words.collect::<Vec<_>>().join(" ");
+    let (addr, hostname) = parse_report_target(&target)?;
+    Some(SimpleHop {
+        ttl: Some(ttl),
+        addr,
+        hostname: hostname.map(str::to_string),
+        rtt,
+        loss: None,
+        jitter: None,
+    })
+}
+
+/// Parse `name (address)` or a bare address, as nmap writes targets and hops.
+fn parse_report_target(target: &str) -> Option<(IpAddr, Option<&str>)> {
+    match target
+        .trim()
+        .strip_suffix(')')
+        .and_then(|target| target.rsplit_once(" ("))
+    {
+        Some((name, addr)) => Some((addr.parse().ok()?, Some(name))),
+        None => Some((target.trim().parse().ok()?, None)),
+    }
+}
+
+fn scanned_host(addr: IpAddr, hostname: Option<&str>) -> SimpleHost {
+    let mut host = SimpleHost::new(addr);
+    host.scanned = true;
+    host.main_hostname = hostname.and_then(|name| Dname::from_chars(name.chars()).ok());
+    // Names given as targets or found by reverse DNS cannot be told apart here.
+    host.hostnames = hostname
+        .iter()
+        .map(|name| SimpleHostname {
+            name: name.to_string(),
+            kind: None,
+        })
+        .collect();
+    host
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host<'a>(topology: &'a Topology, addr: &str) -> &'a SimpleHost {
+        &topology.graph[topology.find_host(addr.parse().unwrap()).unwrap()]
+    }
+
+    fn trace_addresses(host: &SimpleHost) -> Vec<String> {
+        host.trace.iter().map(|hop| hop.addr.to_string()).collect()
+    }
+
+    #[test]
+    fn grepable_hosts_seen_twice_are_merged() {
+        let output = "# Nmap 7.94 scan initiated as: nmap -sV -oG - 10.0.0.0/30\n\
+            Host: 10.0.0.1 (gw.example.net)\tStatus: Up\n\
+            Host: 10.0.0.1 (gw.example.net)\tPorts: 22/open/tcp//ssh//OpenSSH 8.9p1/, 80/closed/tcp//http///\tIgnored State: filtered (998)\n\
+            Host: 10.0.0.2 ()\tStatus: Down\n\
+            # Nmap done at Mon Jan  1 00:00:00 2024 -- 4 IP addresses (1 host up) scanned in 2.00 seconds\n";
+        let loaded = import_grepable(output.as_bytes()).unwrap();
+
+        assert_eq!(loaded.hosts_down, 1);
+        // The scanner and the one host that is up.
+        assert_eq!(loaded.topology.hosts().count(), 2);
+        let gateway = host(&loaded.topology, "10.0.0.1");
+        assert_eq!(gateway.hostnames.len(), 1);
+        assert_eq!(gateway.ports.len(), 2);
+        assert_eq!(gateway.ports[0].portid, 22);
+        assert_eq!(gateway.ports[0].service.as_deref(), Some("ssh"));
+        assert_eq!(gateway.ports[0].product.as_deref(), Some("OpenSSH 8.9p1"));
+        assert_eq!(gateway.ports[1].state, "closed");
+    }
+
+    #[test]
+    fn normal_output_skips_down_hosts_and_copies_shared_hops() {
+        let output = "Starting Nmap 7.94 ( https://nmap.org ) at 2024-01-01 00:00 UTC\n\
+            Nmap scan report for 10.0.0.9 [host down]\n\
+            Nmap scan report for web.example.net (10.0.1.5)\n\
+            Host is up (0.0020s latency).\n\
+            Not shown: 998 closed tcp ports (reset)\n\
+            PORT   STATE SERVICE VERSION\n\
+            22/tcp open  ssh     OpenSSH 8.9p1 Ubuntu\n\
+            80/tcp open  http    nginx 1.18.0\n\
+            \n\
+            TRACEROUTE (using port 80/tcp)\n\
+            HOP RTT     ADDRESS\n\
+            1   0.50 ms gw.example.net (10.0.0.1)\n\
+            2   1.20 ms 10.0.1.1\n\
+            3   2.00 ms web.example.net (10.0.1.5)\n\
+            \n\
+            Nmap scan report for 10.0.1.6\n\
+            Host is up (0.0021s latency).\n\
+            All 1000 scanned ports on 10.0.1.6 are in ignored states.\n\
+            \n\
+            TRACEROUTE (using port 80/tcp)\n\
+            HOP RTT     ADDRESS\n\
+            -   Hops 1-2 are the same as for 10.0.1.5\n\
+            3   2.10 ms 10.0.1.6\n\
+            \n\
+            Nmap done: 3 IP addresses (2 hosts up) scanned in 5.00 seconds\n";
+        let loaded = import_normal(output.as_bytes()).unwrap();
+        let topology = &loaded.topology;
+
+        assert_eq!(loaded.hosts_down, 1);
+        assert!(topology.find_host("10.0.0.9".parse().unwrap()).is_none());
+
+        let web = host(topology, "10.0.1.5");
+        assert_eq!(web.ports.len(), 2);
+        assert_eq!(
+            web.ports[0].product.as_deref(),
+            Some("OpenSSH 8.9p1 Ubuntu")
+        );
+        assert_eq!(trace_addresses(web), ["10.0.0.1", "10.0.1.1", "10.0.1.5"]);
+
+        let neighbour = host(topology, "10.0.1.6");
+        assert!(neighbour.ports.is_empty());
+        assert!(neighbour.ports_scanned);
+        assert_eq!(
+            trace_addresses(neighbour),
+            ["10.0.0.1", "10.0.1.1", "10.0.1.6"]
+        );
+        // The shared hops lead to the host instead of it hanging off the scanner.
+        let router = topology.find_host("10.0.1.1".parse().unwrap()).unwrap();
+        let neighbour = topology.find_host("10.0.1.6".parse().unwrap()).unwrap();
+        assert!(topology.graph.find_edge(router, neighbour).is_some());
+        assert!(topology
+            .graph
+            .find_edge(Topology::root(), neighbour)
+            .is_none());
+    }
+
+    #[test]
+    fn shared_hop_rows() {
+        let web = "10.0.1.5".parse().unwrap();
+        assert_eq!(
+            parse_shared_hops("-   Hops 1-3 are the same as for 10.0.1.5"),
+            Some((3, web))
+        );
+        assert_eq!(
+            parse_shared_hops("-   Hop 1 is the same as for web.example.net (10.0.1.5)"),
+            Some((1, web))
+        );
+        assert_eq!(parse_shared_hops("3   2.10 ms 10.0.1.6"), None);
+    }
+}
@@ -0,0 +1,513 @@
+//! Drawing the laid-out topology without a window, as a PNG or SVG seen through an orbit camera, for
+//! `neuromaster render` and the picture in the HTML report.
+
+use crate::export::escape_xml;
+use crate::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::import;
+use crate::labels::{self, LabelMode, LabelPlacer};
+use crate::layout::Layout;
+use crate::stream;
+use crate::topology::Topology;
+use image::{Rgb, RgbImage};
+use nalgebra::{Point3, Vector3};
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+// The same palette as the viewer.
+const BACKGROUND_COLOR: [u8; 3] = [0, 0, 0];
+const NODE_COLOR: [u8; 3] = [255, 0, 0];
+const LINE_COLOR: [u8; 3] = [0, 255, 0];
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+
+const NODE_RADIUS: f32 = 1.0;
+// Nodes never shrink below this many pixels, so distant hosts stay visible.
+const MIN_NODE_PIXELS: f32 = 2.0;
+// Bitmap font pixels per glyph pixel.
+const LABEL_SCALE: u32 = 2;
+
+pub const DEFAULT_WIDTH: u32 = 1920;
+pub const DEFAULT_HEIGHT: u32 = 1080;
+// The same vertical field of view as kiss3d's ArcBall.
+pub const DEFAULT_FOV: f32 = FRAC_PI_4;
+// Like kiss3d's ArcBall, the camera never looks straight down or up, where its right and up directions
+// are undefined.
+const PITCH_LIMIT: f32 = 0.01;
+// Distance from the eye of the plane anything nearer is clipped against.
+const NEAR_PLANE: f32 = NODE_RADIUS * 0.1;
+
+/// A camera orbiting a target point, with the same angles as kiss3d's ArcBall.
+#[derive(Clone, Debug)]
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    /// Rotation around the vertical axis, in radians.
+    pub yaw: f32,
+    /// Angle from the vertical axis, in radians.
+    pub pitch: f32,
+    pub distance: f32,
+    /// Vertical field of view, in radians.
+    pub fov: f32,
+}
+
+impl OrbitCamera {
+    /// Look at the whole graph from the viewer's initial direction.
+    pub fn fit(layout: &Layout) -> Self {
+        let positions: Vec<Point3<f32>> = layout
+            .positions()
+            .map(|(_, position)| Point3::from(position))
+            .collect();
+        let count = positions.len().max(1) as f32;
+        let target = Point3::from(
+            positions
+                .iter()
+                .fold(Vector3::zeros(), |sum, position| sum + position.coords)
+                / count,
+        );
+        let radius = positions
+            .iter()
+            .map(|position| (position - target).norm())
+            .fold(NODE_RADIUS, f32::max);
+        let fov = DEFAULT_FOV;
+
+        OrbitCamera {
+            target,
+            yaw: -FRAC_PI_2,
+            pitch: FRAC_PI_2,
+            distance: radius / (fov / 2.0).sin() + NODE_RADIUS,
+            fov,
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let pitch = self.pitch.clamp(PITCH_LIMIT, PI - PITCH_LIMIT);
+        Point3::new(
+            self.target.x + self.distance * self.yaw.cos() * pitch.sin(),
+            self.target.y + self.distance * pitch.cos(),
+            self.target.z + self.distance * self.yaw.sin() * pitch.sin(),
+        )
+    }
+
+    /// A point in camera space: its distances along the right, up and view directions.
+    fn view(&self, point: &Point3<f32>) -> Vector3<f32> {
+        let eye = self.eye();
+        let forward = (self.target - eye).normalize();
+        let right = forward.cross(&Vector3::y()).normalize();
+        let up = right.cross(&forward);
+
+        let offset = point - eye;
+        Vector3::new(offset.dot(&right), offset.dot(&up), offset.dot(&forward))
+    }
+
+    fn to_screen(&self, view: &Vector3<f32>, width: u32, height: u32) -> (f32, f32) {
+        let focal = 1.0 / (self.fov / 2.0).tan() * height as f32 / 2.0;
+        (
+            width as f32 / 2.0 + view.x * focal / view.z,
+            height as f32 / 2.0 - view.y * focal / view.z,
+        )
+    }
+
+    /// Project a point to pixel coordinates, returning None if it is behind the camera.
+    /// The third value is the distance along the view direction.
+    pub fn project(&self, point: &Point3<f32>, width: u32, height: u32) -> Option<(f32, f32, f32)> {
+        let view = self.view(point);
+        if view.z <= NEAR_PLANE {
+            return None;
+        }
+        let (x, y) = self.to_screen(&view, width, height);
+        Some((x, y, view.z))
+    }
+
+    /// Project a line to pixel coordinates, cutting off any part of it behind the camera.
+    /// Returns None if all of it is.
+    pub fn project_line(
+        &self,
+        start: &Point3<f32>,
+        end: &Point3<f32>,
+        width: u32,
+        height: u32,
+    ) -> Option<(f32, f32, f32, f32)> {
+        let (mut start, mut end) = (self.view(start), self.view(end));
+        if start.z <= NEAR_PLANE && end.z <= NEAR_PLANE {
+            return None;
+        }
+        // Move the end that is behind the camera along the line up to the near plane.
+        let clip = |behind: Vector3<f32>, front: Vector3<f32>| {
+            behind + (front - behind) * ((NEAR_PLANE - behind.z) / (front.z - behind.z))
+        };
+        if start.z < NEAR_PLANE {
+            start = clip(start, end);
+        } else if end.z < NEAR_PLANE {
+            end = clip(end, start);
+        }
+        let (x0, y0) = self.to_screen(&start, width, height);
+        let (x1, y1) = self.to_screen(&end, width, height);
+        Some((x0, y0, x1, y1))
+    }
+}
+
+/// What to draw and how.
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub camera: OrbitCamera,
+    /// None draws no labels.
+    pub label_mode: Option<LabelMode>,
+    /// Hosts left out of the picture, along with their edges.
+    pub hidden: HashSet<IpAddr>,
+}
+
+struct ProjectedNode {
+    x: f32,
+    y: f32,
+    radius: f32,
+    depth: f32,
+    label: Option<String>,
+}
+
+/// The graph flattened to screen space.
+struct Scene {
+    nodes: Vec<ProjectedNode>,
+    lines: Vec<(f32, f32, f32, f32)>,
+}
+
+impl Scene {
+    fn project(topology: &Topology, layout: &Layout, options: &RenderOptions) -> Self {
+        let (width, height) = (options.width, options.height);
+        let visible = |host_index| {
+            !options
+                .hidden
+                .contains(&topology.graph[host_index].main_addr)
+        };
+        let location = |host_index| Point3::from(layout.position(host_index));
+        let project = |host_index| {
+            if !visible(host_index) {
+                return None;
+            }
+            options.camera.project(&location(host_index), width, height)
+        };
+
+        let lines = topology
+            .link_indices()
+            .filter_map(|link| {
+                let (a, b) = topology.graph.edge_endpoints(link)?;
+                if !visible(a) || !visible(b) {
+                    return None;
+                }
+                options
+                    .camera
+                    .project_line(&location(a), &location(b), width, height)
+            })
+            .collect();
+
+        let focal = 1.0 / (options.camera.fov / 2.0).tan() * height as f32 / 2.0;
+        let mut nodes: Vec<ProjectedNode> = topology
+            .host_indices()
+            .filter_map(|host_index| {
+                let (x, y, depth) = project(host_index)?;
+                Some(ProjectedNode {
+                    x,
+                    y,
+                    radius: (NODE_RADIUS * focal / depth).max(MIN_NODE_PIXELS),
+                    depth,
+                    label: options
+                        .label_mode
+                        .as_ref()
+                        .map(|mode| labels::format_label(&topology.graph[host_index], mode)),
+                })
+            })
+            .collect();
+
+        // Nearer labels win when they would overlap.
+        nodes.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+        let mut placer = LabelPlacer::default();
+        for node in nodes.iter_mut() {
+            if let Some(label) = &node.label {
+                let (label_x, label_y) = label_origin(node);
+                let label_width =
+                    (label.chars().count() as u32 * (GLYPH_WIDTH + 1) * LABEL_SCALE) as f32;
+                let label_height = (GLYPH_HEIGHT * LABEL_SCALE) as f32;
+                if !placer.try_place(label_x, label_y, label_width, label_height) {
+                    node.label = None;
+                }
+            }
+        }
+
+        Scene { nodes, lines }
+    }
+}
+
+// Labels sit to the right of their node, vertically centred on it.
+fn label_origin(node: &ProjectedNode) -> (f32, f32) {
+    (
+        node.x + node.radius + 4.0,
+        node.y - (GLYPH_HEIGHT * LABEL_SCALE) as f32 / 2.0,
+    )
+}
+
+/// Rasterize the graph into an image.
+pub fn render_image(topology: &Topology, layout: &Layout, options: &RenderOptions) -> RgbImage {
+    let scene = Scene::project(topology, layout, options);
+    let mut image = RgbImage::from_pixel(options.width, options.height, Rgb(BACKGROUND_COLOR));
+
+    for (x0, y0, x1, y1) in &scene.lines {
+        draw_line(&mut image, *x0, *y0, *x1, *y1, Rgb(LINE_COLOR));
+    }
+    for node in &scene.nodes {
+        draw_circle(&mut image, node.x, node.y, node.radius, Rgb(NODE_COLOR));
+    }
+    for node in &scene.nodes {
+        if let Some(label) = &node.label {
+            let (x, y) = label_origin(node);
+            draw_text(&mut image, x, y, label, Rgb(TEXT_COLOR));
+        }
+    }
+    image
+}
+
+/// Write the graph as an SVG document.
+pub fn render_svg<W: Write>(
+    topology: &Topology,
+    layout: &Layout,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scene = Scene::project(topology, layout, options);
+    let hex = |[r, g, b]: [u8; 3]| format!("#{r:02x}{g:02x}{b:02x}");
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        options.width, options.height
+    )?;
+    writeln!(
+        writer,
+        r#"  <rect width="100%" height="100%" fill="{}"/>"#,
+        hex(BACKGROUND_COLOR)
+    )?;
+
+    writeln!(
+        writer,
+        r#"  <g stroke="{}" stroke-width="1">"#,
+        hex(LINE_COLOR)
+    )?;
+    for (x0, y0, x1, y1) in &scene.lines {
+        writeln!(
+            writer,
+            r#"    <line x1="{x0:.1}" y1="{y0:.1}" x2="{x1:.1}" y2="{y1:.1}"/>"#
+        )?;
+    }
+    writeln!(writer, "  </g>")?;
+
+    writeln!(
+        writer,
+        r#"  <g stroke="{}" stroke-width="1" fill="none">"#,
+        hex(NODE_COLOR)
+    )?;
+    for node in &scene.nodes {
+        writeln!(
+            writer,
+            r#"    <circle cx="{:.1}" cy="{:.1}" r="{:.1}"/>"#,
+            node.x, node.y, node.radius
+        )?;
+    }
+    writeln!(writer, "  </g>")?;
+
+    writeln!(
+        writer,
+        r#"  <g fill="{}" font-family="monospace" font-size="{}">"#,
+        hex(TEXT_COLOR),
+        GLYPH_HEIGHT * LABEL_SCALE
+    )?;
+    for node in &scene.nodes {
+        if let Some(label) = &node.label {
+            let (x, y) = label_origin(node);
+            writeln!(
+                writer,
+                r#"    <text x="{x:.1}" y="{:.1}">{}</text>"#,
+                y + (GLYPH_HEIGHT * LABEL_SCALE) as f32,
+                escape_xml(label)
+            )?;
+        }
+    }
+    writeln!(writer, "  </g>")?;
+    writeln!(writer, "</svg>")?;
+    Ok(())
+}
+
+/// Write the graph to a PNG or SVG file, chosen by the extension.
+pub fn render_file(
+    topology: &Topology,
+    layout: &Layout,
+    options: &RenderOptions,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            render_svg(topology, layout, options, &mut writer)?;
+            writer.flush()?;
+        }
+        Some("png") => render_image(topology, layout, options).save(path)?,
+        _ => return Err(format!("{}: expected a .png or .svg output", path.display()).into()),
+    }
+    Ok(())
+}
+
+// Clip a segment to the image with Liang-Barsky, so far-off endpoints cost nothing.
+fn clip_line(
+    (x0, y0, x1, y1): (f32, f32, f32, f32),
+    width: f32,
+    height: f32,
+) -> Option<(f32, f32, f32, f32)> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut start, mut end) = (0.0f32, 1.0f32);
+    for (p, q) in [
+        (-dx, x0),
+        (dx, width - 1.0 - x0),
+        (-dy, y0),
+        (dy, height - 1.0 - y0),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                start = start.max(r);
+            } else {
+                end = end.min(r);
+            }
+            if start > end {
+                return None;
+            }
+        }
+    }
+    Some((
+        x0 + start * dx,
+        y0 + start * dy,
+        x0 + end * dx,
+        y0 + end * dy,
+    ))
+}
+
+fn put_pixel(image: &mut RgbImage, x: f32, y: f32, color: Rgb<u8>) {
+    let (x, y) = (x.round(), y.round());
+    if x >= 0.0 && y >= 0.0 && (x as u32) < image.width() && (y as u32) < image.height() {
+        image.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+fn draw_line(image: &mut RgbImage, x0: f32, y0: f32, x1: f32, y1: f32, color: Rgb<u8>) {
+    let size = (image.width() as f32, image.height() as f32);
+    let Some((x0, y0, x1, y1)) = clip_line((x0, y0, x1, y1), size.0, size.1) else {
+        return;
+    };
+    let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0);
+    for step in 0..=steps as u32 {
+        let t = step as f32 / steps;
+        put_pixel(image, x0 + (x1 - x0) * t, y0 + (y1 - y0) * t, color);
+    }
+}
+
+fn draw_circle(image: &mut RgbImage, cx: f32, cy: f32, radius: f32, color: Rgb<u8>) {
+    // Enough points that neighbours touch.
+    let steps = (radius * std::f32::consts::TAU).ceil().max(8.0) as u32;
+    for step in 0..steps {
+        let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+        put_pixel(
+            image,
+            cx + radius * angle.cos(),
+            cy + radius * angle.sin(),
+            color,
+        );
+    }
+}
+
+fn draw_text(image: &mut RgbImage, x: f32, y: f32, text: &str, color: Rgb<u8>) {
+    for (column, character) in text.chars().enumerate() {
+        let glyph_x = x + (column as u32 * (GLYPH_WIDTH + 1) * LABEL_SCALE) as f32;
+        for (row, bits) in font::glyph(character).iter().enumerate() {
+            for bit in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - bit)) == 0 {
+                    continue;
+                }
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        put_pixel(
+                            image,
+                            glyph_x + (bit * LABEL_SCALE + dx) as f32,
+                            y + (row as u32 * LABEL_SCALE + dy) as f32,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parse the `--labels` option.
+pub fn parse_label_mode(mode: &str) -> Result<LabelMode, Box<dyn std::error::Error>> {
+    match mode {
+        "ip" => Ok(LabelMode::Ip),
+        "hostname" => Ok(LabelMode::Hostname),
+        "both" => Ok(LabelMode::Both),
+        other => Err(format!("unknown label mode {other}").into()),
+    }
+}
+
+/// Entry point for `neuromaster render`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut width = DEFAULT_WIDTH;
+    let mut height = DEFAULT_HEIGHT;
+    let mut yaw = None;
+    let mut pitch = None;
+    let mut distance = None;
+    let mut label_mode = Some(LabelMode::Ip);
+    let mut hidden = HashSet::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--width" => width = value(&arg)?.parse()?,
+            "--height" => height = value(&arg)?.parse()?,
+            "--yaw" => yaw = Some(value(&arg)?.parse::<f32>()?.to_radians()),
+            "--pitch" => pitch = Some(value(&arg)?.parse::<f32>()?.to_radians()),
+            "--distance" => distance = Some(value(&arg)?.parse::<f32>()?),
+            "--labels" => label_mode = Some(parse_label_mode(&value(&arg)?)?),
+            "--label-template" => label_mode = Some(LabelMode::Template(value(&arg)?)),
+            "--no-labels" => label_mode = None,
+            "--hide" => {
+                hidden.insert(value(&arg)?.parse::<IpAddr>()?);
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let output = output.ok_or("render requires an output file: -o <file.png|file.svg>")?;
+
+    let topology = import::load_inputs(&inputs, stream::report_progress)?.topology;
+    stream::end_progress();
+    let mut layout = Layout::new(&topology);
+    layout.settle();
+
+    let mut camera = OrbitCamera::fit(&layout);
+    camera.yaw = yaw.unwrap_or(camera.yaw);
+    camera.pitch = pitch.unwrap_or(camera.pitch);
+    camera.distance = distance.unwrap_or(camera.distance);
+
+    let options = RenderOptions {
+        width,
+        height,
+        camera,
+        label_mode,
+        hidden,
+    };
+    render_file(&topology, &layout, &options, &output)
+}
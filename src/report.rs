@@ -0,0 +1,408 @@
+//! A single self-contained HTML page describing a scan: a summary, a table of hosts, a section for each
+//! scanned host and a static picture of the laid-out topology. Styles and the picture are inlined, so the
+//! page opens anywhere without fetching anything.
+
+use crate::export::escape_xml;
+use crate::import;
+use crate::inventory::{self, InventoryRow};
+use crate::labels::LabelMode;
+use crate::layout::Layout;
+use crate::model::SimpleHost;
+use crate::render::{self, OrbitCamera, RenderOptions};
+use crate::stats::ScanStats;
+use crate::stream;
+use crate::topology::Topology;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 1200px; color: #111; }
+h1, h2, h3 { font-weight: normal; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; vertical-align: top; }
+th { background: #eee; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+figure { margin: 1em 0; }
+figure svg { max-width: 100%; height: auto; }
+section.host { border-top: 1px solid #ccc; margin-top: 2em; }
+";
+
+/// The anchor of a host's detail section.
+fn host_anchor(host: &SimpleHost) -> String {
+    format!("host-{}", host.main_addr)
+}
+
+fn write_summary<W: Write>(stats: &ScanStats, writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "<h2>Summary</h2>")?;
+    writeln!(writer, "<table>")?;
+    let graph = &stats.graph;
+    for (name, value) in [
+        ("Hosts up", stats.hosts_up.to_string()),
+        ("Hosts down", stats.hosts_down.to_string()),
+        ("Routers", stats.routers.to_string()),
+        ("Links", graph.edges.to_string()),
+        ("Max depth from scanner", graph.max_depth.to_string()),
+        ("Unreachable from scanner", graph.unreachable.to_string()),
+    ] {
+        writeln!(writer, "<tr><th>{name}</th><td>{value}</td></tr>")?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h3>OS families</h3>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(writer, "<tr><th>Family</th><th>Hosts</th></tr>")?;
+    for (family, hosts) in &stats.os_families {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td>{hosts}</td></tr>",
+            escape_xml(family)
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h3>Most common open ports</h3>")?;
+    writeln!(writer, "<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Port</th><th>Service</th><th>Hosts</th></tr>"
+    )?;
+    for port in &stats.top_ports {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape_xml(&port.port),
+            escape_xml(port.service.as_deref().unwrap_or_default()),
+            port.hosts
+        )?;
+    }
+    writeln!(writer, "</table>")
+}
+
+fn write_host_table<W: Write>(rows: &[InventoryRow], writer: &mut W) -> std::io::Result<()> {
+    writeln!(writer, "<h2>Hosts</h2>")?;
+    writeln!(writer, "<table>")?;
+    write!(writer, "<tr>")?;
+    for column in inventory::COLUMNS {
+        write!(writer, "<th>{column}</th>")?;
+    }
+    writeln!(writer, "</tr>")?;
+    for row in rows {
+        write!(writer, "<tr>")?;
+        for (column, cell) in row.cells().iter().enumerate() {
+            // Scanned hosts link to their detail sections.
+            if column == 0 && row.host.scanned {
+                write!(
+                    writer,
+                    "<td><a href=\"#{}\">{}</a></td>",
+                    host_anchor(&row.host),
+                    escape_xml(cell)
+                )?;
+            } else {
+                write!(writer, "<td>{}</td>", escape_xml(cell))?;
+            }
+        }
+        writeln!(writer, "</tr>")?;
+    }
+    writeln!(writer, "</table>")
+}
+
+fn write_host_section<W: Write>(row: &InventoryRow, writer: &mut W) -> std::io::Result<()> {
+    let host = &row.host;
+    writeln!(
+        writer,
+        "<section class=\"host\" id=\"{}\">",
+        host_anchor(host)
+    )?;
+    match host.display_hostname() {
+        Some(hostname) => writeln!(
+            writer,
+            "<h3>{} ({})</h3>",
+            host.main_addr,
+            escape_xml(&hostname)
+        )?,
+        None => writeln!(writer, "<h3>{}</h3>", host.main_addr)?,
+    }
+    if let Some(mac) = &host.mac {
+        writeln!(writer, "<p>MAC address: {}</p>", escape_xml(mac))?;
+    }
+
+    if !host.ports.is_empty() {
+        writeln!(writer, "<h4>Ports</h4>")?;
+        writeln!(writer, "<table>")?;
+        writeln!(
+            writer,
+            "<tr><th>Port</th><th>State</th><th>Service</th><th>Product</th><th>Version</th></tr>"
+        )?;
+        for port in &host.ports {
+            writeln!(
+                writer,
+                "<tr><td>{}/{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                port.portid,
+                escape_xml(&port.protocol),
+                escape_xml(&port.state),
+                escape_xml(port.service.as_deref().unwrap_or_default()),
+                escape_xml(port.product.as_deref().unwrap_or_default()),
+                escape_xml(port.version.as_deref().unwrap_or_default())
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+    }
+
+    if !host.scripts.is_empty() {
+        writeln!(writer, "<h4>Scripts</h4>")?;
+        for script in &host.scripts {
+            match &script.port {
+                Some(port) => writeln!(
+                    writer,
+                    "<h5>{} on {}</h5>",
+                    escape_xml(&script.id),
+                    escape_xml(port)
+                )?,
+                None => writeln!(writer, "<h5>{}</h5>", escape_xml(&script.id))?,
+            }
+            writeln!(writer, "<pre>{}</pre>", escape_xml(script.output.trim()))?;
+        }
+    }
+
+    // Formats without OS candidates still carry the single best match.
+    if !host.os_matches.is_empty() {
+        writeln!(writer, "<h4>OS candidates</h4>")?;
+        writeln!(writer, "<ol>")?;
+        for os_match in &host.os_matches {
+            match os_match.accuracy {
+                Some(accuracy) => writeln!(
+                    writer,
+                    "<li>{} ({accuracy}%)</li>",
+                    escape_xml(&os_match.name)
+                )?,
+                None => writeln!(writer, "<li>{}</li>", escape_xml(&os_match.name))?,
+            }
+        }
+        writeln!(writer, "</ol>")?;
+    } else if let Some(os) = &host.os_guess {
+        writeln!(writer, "<p>OS: {}</p>", escape_xml(os.name()))?;
+    }
+
+    if !host.trace.is_empty() {
+        writeln!(writer, "<h4>Trace</h4>")?;
+        writeln!(writer, "<table>")?;
+        writeln!(
+            writer,
+            "<tr><th>TTL</th><th>Address</th><th>Hostname</th><th>RTT</th><th>Loss</th><th>Jitter</th></tr>"
+        )?;
+        for hop in &host.trace {
+            let ms = |value: Option<f32>| value.map(|ms| format!("{ms:.2} ms")).unwrap_or_default();
+            writeln!(
+                writer,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                hop.ttl.map(|ttl| ttl.to_string()).unwrap_or_default(),
+                hop.addr,
+                escape_xml(hop.hostname.as_deref().unwrap_or_default()),
+                ms(hop.rtt),
+                hop.loss
+                    .map(|loss| format!("{loss:.1}%"))
+                    .unwrap_or_default(),
+                ms(hop.jitter)
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+    }
+    writeln!(writer, "</section>")
+}
+
+/// Write the report, drawing the topology as it is currently laid out.
+pub fn write_report<W: Write>(
+    title: &str,
+    stats: &ScanStats,
+    topology: &Topology,
+    layout: &Layout,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let rows = inventory::inventory(topology, |_, _| true);
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>")?;
+    writeln!(writer, "<head>")?;
+    writeln!(writer, "<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>{}</title>", escape_xml(title))?;
+    writeln!(writer, "<style>{STYLE}</style>")?;
+    writeln!(writer, "</head>")?;
+    writeln!(writer, "<body>")?;
+    writeln!(writer, "<h1>{}</h1>", escape_xml(title))?;
+
+    write_summary(stats, writer)?;
+
+    writeln!(writer, "<h2>Topology</h2>")?;
+    writeln!(writer, "<figure>")?;
+    render::render_svg(topology, layout, options, writer)?;
+    writeln!(writer, "</figure>")?;
+
+    write_host_table(&rows, writer)?;
+
+    writeln!(writer, "<h2>Host details</h2>")?;
+    for row in rows.iter().filter(|row| row.host.scanned) {
+        write_host_section(row, writer)?;
+    }
+
+    writeln!(writer, "</body>")?;
+    writeln!(writer, "</html>")?;
+    Ok(())
+}
+
+/// Write the report to a file.
+pub fn save_report(
+    title: &str,
+    stats: &ScanStats,
+    topology: &Topology,
+    layout: &Layout,
+    options: &RenderOptions,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_report(title, stats, topology, layout, options, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Title a report after the files it was made from.
+pub fn default_title(inputs: &[PathBuf]) -> String {
+    let names: Vec<String> = inputs
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    if names.is_empty() {
+        "neuromaster report".to_string()
+    } else {
+        format!("neuromaster report: {}", names.join(", "))
+    }
+}
+
+/// Entry point for `neuromaster report`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut title = None;
+    let mut width = render::DEFAULT_WIDTH;
+    let mut height = render::DEFAULT_HEIGHT;
+    let mut label_mode = Some(LabelMode::Ip);
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--title" => title = Some(value(&arg)?),
+            "--width" => width = value(&arg)?.parse()?,
+            "--height" => height = value(&arg)?.parse()?,
+            "--labels" => label_mode = Some(render::parse_label_mode(&value(&arg)?)?),
+            "--label-template" => label_mode = Some(LabelMode::Template(value(&arg)?)),
+            "--no-labels" => label_mode = None,
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    let output = output.ok_or("report requires an output file: -o <file.html>")?;
+
+    let loaded = import::load_inputs(&inputs, stream::report_progress)?;
+    stream::end_progress();
+    let mut layout = Layout::new(&loaded.topology);
+    layout.settle();
+
+    let options = RenderOptions {
+        width,
+        height,
+        camera: OrbitCamera::fit(&layout),
+        label_mode,
+        hidden: HashSet::new(),
+    };
+    save_report(
+        &title.unwrap_or_else(|| default_title(&inputs)),
+        &ScanStats::from_loaded(&loaded),
+        &loaded.topology,
+        &layout,
+        &options,
+        &output,
+    )?;
+    eprintln!("Wrote report to {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A host whose scan output holds markup, which must come out as text.
+    fn report(title: &str) -> String {
+        let topology = import::import_json(
+            br#"{
+                "hosts": [{
+                    "address": "10.0.0.2",
+                    "hostnames": ["web.example.net"],
+                    "os": "Linux <b>5.4</b> & co",
+                    "ports": [{ "portid": 80, "service": "http", "product": "<img src=x onerror=alert(1)>" }],
+                    "trace": [
+                        { "ttl": 1, "address": "10.0.0.1", "hostname": "\"gw\"" },
+                        { "ttl": 2, "address": "10.0.0.2" }
+                    ]
+                }]
+            }"#,
+        )
+        .unwrap();
+        let layout = Layout::new(&topology);
+        let options = RenderOptions {
+            width: render::DEFAULT_WIDTH,
+            height: render::DEFAULT_HEIGHT,
+            camera: OrbitCamera::fit(&layout),
+            label_mode: Some(LabelMode::Ip),
+            hidden: HashSet::new(),
+        };
+        let mut bytes = Vec::new();
+        write_report(
+            title,
+            &ScanStats::new(&topology, 0),
+            &topology,
+            &layout,
+            &options,
+            &mut bytes,
+        )
+        .unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn reports_escape_scan_output() {
+        let html = report("scans <of> lab & office");
+        assert!(html.contains("<title>scans &lt;of&gt; lab &amp; office</title>"));
+        assert!(html.contains("<h1>scans &lt;of&gt; lab &amp; office</h1>"));
+        assert!(html.contains("Linux &lt;b&gt;5.4&lt;/b&gt; &amp; co"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+        assert!(html.contains("<td>&quot;gw&quot;</td>"));
+        assert!(!html.contains("<img"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn reports_fetch_nothing() {
+        let html = report("lab");
+        for reference in [
+            "<script", "<link", "<iframe", "<img", "src=\"", "url(", "@import",
+        ] {
+            assert!(!html.contains(reference), "report contains {reference}");
+        }
+        // Links only lead to the host sections further down.
+        assert_eq!(
+            html.matches("href=\"").count(),
+            html.matches("href=\"#").count()
+        );
+        // The only address in the page is the SVG namespace, which names rather than locates.
+        assert_eq!(
+            html.matches("http://").count(),
+            html.matches(r#"xmlns="http://www.w3.org/2000/svg""#)
+                .count()
+        );
+        assert!(html.contains(r#"<section class="host" id="host-10.0.0.2">"#));
+        assert!(html.contains(r##"<a href="#host-10.0.0.2">"##));
+    }
+}
@@ -0,0 +1,264 @@
+//! Running nmap as a subprocess and streaming its XML output into a load.
+
+use crate::stream::{self, LoadMessage};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+pub const DEFAULT_NMAP: &str = "nmap";
+
+/// The kinds of scan offered in the viewer's scan panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanProfile {
+    PingSweep,
+    TopPorts,
+    Aggressive,
+}
+
+impl ScanProfile {
+    pub fn next(self) -> Self {
+        match self {
+            ScanProfile::PingSweep => ScanProfile::TopPorts,
+            ScanProfile::TopPorts => ScanProfile::Aggressive,
+            ScanProfile::Aggressive => ScanProfile::PingSweep,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScanProfile::PingSweep => "ping sweep",
+            ScanProfile::TopPorts => "top ports",
+            ScanProfile::Aggressive => "-A with traceroute",
+        }
+    }
+
+    /// The profile that looks harder at hosts found with this one.
+    pub fn deeper(self) -> Self {
+        match self {
+            ScanProfile::PingSweep => ScanProfile::TopPorts,
+            ScanProfile::TopPorts | ScanProfile::Aggressive => ScanProfile::Aggressive,
+        }
+    }
+
+    pub fn arguments(self) -> &'static [&'static str] {
+        match self {
+            ScanProfile::PingSweep => &["-sn"],
+            ScanProfile::TopPorts => &["--top-ports", "100"],
+            ScanProfile::Aggressive => &["-A", "--traceroute"],
+        }
+    }
+}
+
+/// Start nmap on the targets, writing XML to stdout, and stream the hosts it reports.
+/// A failing exit status is reported in place of whatever went wrong reading its output.
+pub fn spawn_scan(
+    nmap: &Path,
+    profile: ScanProfile,
+    targets: &[String],
+) -> Result<Receiver<LoadMessage>, Box<dyn std::error::Error>> {
+    // Targets come from the scan panel, and nmap takes anything starting with a dash as an option.
+    if let Some(target) = targets.iter().find(|target| target.starts_with('-')) {
+        return Err(format!("{target} is not a scan target.").into());
+    }
+    let mut child = Command::new(nmap)
+        .args(live_arguments(profile, targets))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or("Could not read nmap's output.")?;
+    let messages = stream::spawn_loader(stdout, None);
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for message in messages {
+            if let LoadMessage::Finished(error) = message {
+                let error = match child.wait() {
+                    Ok(status) if !status.success() => Some(format!("nmap exited with {status}")),
+                    Ok(_) => error,
+                    Err(e) => Some(e.to_string()),
+                };
+                let _ = sender.send(LoadMessage::Finished(error));
+                return;
+            }
+            // Nobody is watching the scan any more.
+            if sender.send(message).is_err() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+/// The arguments `spawn_scan` runs nmap with.
+fn live_arguments(profile: ScanProfile, targets: &[String]) -> Vec<String> {
+    let mut arguments: Vec<String> = profile
+        .arguments()
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect();
+    arguments.extend(["-oX".to_string(), "-".to_string()]);
+    arguments.extend(targets.iter().cloned());
+    arguments
+}
+
+/// The command line `spawn_scan` runs for the targets, to show before starting it.
+pub fn live_command_line(nmap: &Path, profile: ScanProfile, targets: &[String]) -> String {
+    let mut words = vec![nmap.display().to_string()];
+    words.extend(live_arguments(profile, targets));
+    quote_words(&words)
+}
+
+/// The command line running the same scan over the targets listed in a file, writing XML to the output path.
+pub fn command_line(
+    nmap: &Path,
+    profile: ScanProfile,
+    target_list: &Path,
+    output: &Path,
+) -> String {
+    let mut words = vec![nmap.display().to_string()];
+    words.extend(
+        profile
+            .arguments()
+            .iter()
+            .map(|argument| argument.to_string()),
+    );
+    words.extend([
+        "-oX".to_string(),
+        output.display().to_string(),
+        "-iL".to_string(),
+        target_list.display().to_string(),
+    ]);
+    quote_words(&words)
+}
+
+/// Write the targets one per line, as nmap's `-iL` reads them.
+pub fn write_target_list(targets: &[String], path: &Path) -> io::Result<()> {
+    let mut list = targets.join("\n");
+    list.push('\n');
+    fs::write(path, list)
+}
+
+fn quote_words(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| shell_quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(word: &str) -> String {
+    let is_plain = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,+@".contains(c));
+    if is_plain {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Topology;
+
+    /// Stands in for nmap, printing the same XML whatever it is asked to scan.
+    const STUB_NMAP: &str = r#"#!/bin/sh
+cat <<'EOF'
+<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" args="nmap -sn -oX - 10.0.0.7" start="1700000000" version="7.94" xmloutputversion="1.05">
+<host><status state="up" reason="arp-response" reason_ttl="0"/>
+<address addr="10.0.0.7" addrtype="ipv4"/>
+</host>
+<runstats><finished time="1700000001" elapsed="1.00" exit="success"/><hosts up="1" down="0" total="1"/>
+</runstats>
+</nmaprun>
+EOF
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn scans_stream_their_hosts_into_the_graph() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("neuromaster-scan-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let nmap = dir.join("nmap");
+        fs::write(&nmap, STUB_NMAP).unwrap();
+        fs::set_permissions(&nmap, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut topology = Topology::new().unwrap();
+        let messages =
+            spawn_scan(&nmap, ScanProfile::PingSweep, &["10.0.0.7".to_string()]).unwrap();
+        let mut finished = None;
+        for message in messages {
+            match message {
+                LoadMessage::Host(host) => {
+                    topology.add_scanned_host(host);
+                }
+                LoadMessage::Progress(_) => {}
+                LoadMessage::Finished(error) => finished = Some(error),
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(finished, Some(None));
+        assert_eq!(topology.graph.node_count(), 2);
+        assert!(topology.find_host("10.0.0.7".parse().unwrap()).is_some());
+    }
+
+    #[test]
+    fn targets_cannot_pass_options_to_nmap() {
+        let targets = ["10.0.0.7".to_string(), "-oN/tmp/owned".to_string()];
+        let error = spawn_scan(
+            Path::new("/nonexistent/nmap"),
+            ScanProfile::TopPorts,
+            &targets,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "-oN/tmp/owned is not a scan target.");
+    }
+
+    #[test]
+    fn rescans_go_one_profile_deeper() {
+        assert_eq!(ScanProfile::PingSweep.deeper(), ScanProfile::TopPorts);
+        assert_eq!(ScanProfile::TopPorts.deeper(), ScanProfile::Aggressive);
+        assert_eq!(ScanProfile::Aggressive.deeper(), ScanProfile::Aggressive);
+    }
+
+    #[test]
+    fn command_lines_quote_what_the_shell_would_split() {
+        assert_eq!(
+            command_line(
+                Path::new("/opt/nmap 7.94/nmap"),
+                ScanProfile::TopPorts,
+                Path::new("targets.txt"),
+                Path::new("it's here.xml"),
+            ),
+            r#"'/opt/nmap 7.94/nmap' --top-ports 100 -oX 'it'\''s here.xml' -iL targets.txt"#
+        );
+        assert_eq!(
+            live_command_line(
+                Path::new("nmap"),
+                ScanProfile::Aggressive,
+                &["10.0.0.0/24".to_string(), "host;reboot".to_string()],
+            ),
+            "nmap -A --traceroute -oX - 10.0.0.0/24 'host;reboot'"
+        );
+    }
+
+    #[test]
+    fn target_lists_hold_one_target_per_line() {
+        let path = std::env::temp_dir().join(format!("neuromaster-targets-{}", std::process::id()));
+        write_target_list(&["10.0.0.1".to_string(), "10.0.0.2".to_string()], &path).unwrap();
+        let list = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(list, "10.0.0.1\n10.0.0.2\n");
+    }
+}
@@ -0,0 +1,305 @@
+//! Counts over a loaded scan for `neuromaster stats` and the report's summary: hosts up and down, route
+//! lengths, OS families, the most common open ports and the shape of the graph.
+
+use crate::import::{self, LoadedGraph};
+use crate::model::SimpleHost;
+use crate::stream;
+use crate::topology::{HostIndex, Topology, TopologyGraph};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::PathBuf;
+
+// Open ports listed in the summary, most common first.
+const TOP_PORTS: usize = 10;
+
+/// Summary of a loaded scan and the topology built from it.
+#[derive(Debug, Serialize)]
+pub struct ScanStats {
+    pub hosts_up: usize,
+    pub hosts_down: usize,
+    /// Scanned hosts by number of traceroute hops; 0 counts hosts without a traceroute.
+    pub hop_counts: BTreeMap<usize, usize>,
+    /// Hosts only seen as traceroute hops.
+    pub routers: usize,
+    pub os_families: BTreeMap<String, usize>,
+    pub top_ports: Vec<PortCount>,
+    pub graph: GraphMetrics,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PortCount {
+    pub port: String,
+    pub service: Option<String>,
+    pub hosts: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphMetrics {
+    pub nodes: usize,
+    pub edges: usize,
+    pub average_degree: f32,
+    pub max_degree: usize,
+    pub components: usize,
+    /// Longest shortest path from the scanner, in links.
+    pub max_depth: usize,
+    /// Nodes with no path to the scanner.
+    pub unreachable: usize,
+}
+
+impl ScanStats {
+    pub fn from_loaded(loaded: &LoadedGraph) -> Self {
+        Self::new(&loaded.topology, loaded.hosts_down)
+    }
+
+    /// Summarize a topology, given how many hosts the scans that built it found down.
+    pub fn new(topology: &Topology, hosts_down: usize) -> Self {
+        let scanned: Vec<&SimpleHost> = topology.hosts().filter(|host| host.scanned).collect();
+
+        let mut hop_counts = BTreeMap::new();
+        let mut os_families = BTreeMap::new();
+        let mut ports = HashMap::<(u16, String), (Option<String>, usize)>::new();
+        for host in &scanned {
+            *hop_counts.entry(host.trace.len()).or_insert(0) += 1;
+
+            let family = host.os_guess.as_ref().map_or("unknown", |os| os.family());
+            *os_families.entry(family.to_string()).or_insert(0) += 1;
+
+            for port in host.open_ports() {
+                let entry = ports
+                    .entry((port.portid, port.protocol.clone()))
+                    .or_insert((None, 0));
+                entry.0 = entry.0.take().or_else(|| port.service.clone());
+                entry.1 += 1;
+            }
+        }
+
+        let mut top_ports: Vec<PortCount> = ports
+            .into_iter()
+            .map(|((portid, protocol), (service, hosts))| PortCount {
+                port: format!("{portid}/{protocol}"),
+                service,
+                hosts,
+            })
+            .collect();
+        top_ports.sort_by(|a, b| b.hosts.cmp(&a.hosts).then(a.port.cmp(&b.port)));
+        top_ports.truncate(TOP_PORTS);
+
+        let routers = topology
+            .host_indices()
+            .filter(|host_index| {
+                *host_index != Topology::root() && !topology.graph[*host_index].scanned
+            })
+            .count();
+
+        ScanStats {
+            hosts_up: scanned.len(),
+            hosts_down,
+            hop_counts,
+            routers,
+            os_families,
+            top_ports,
+            graph: GraphMetrics::from_topology(topology),
+        }
+    }
+}
+
+impl GraphMetrics {
+    pub fn from_topology(topology: &Topology) -> Self {
+        let graph = &topology.graph;
+        let nodes = graph.node_count();
+        let degrees: Vec<usize> = graph
+            .node_indices()
+            .map(|node_index| graph.neighbors(node_index).count())
+            .collect();
+
+        let depths = breadth_first_depths(graph, Topology::root());
+        let mut components = 0;
+        let mut visited = HashSet::new();
+        for node_index in graph.node_indices() {
+            if visited.contains(&node_index) {
+                continue;
+            }
+            components += 1;
+            visited.extend(breadth_first_depths(graph, node_index).into_keys());
+        }
+
+        GraphMetrics {
+            nodes,
+            edges: graph.edge_count(),
+            average_degree: if nodes == 0 {
+                0.0
+            } else {
+                degrees.iter().sum::<usize>() as f32 / nodes as f32
+            },
+            max_degree: degrees.iter().copied().max().unwrap_or(0),
+            components,
+            max_depth: depths.values().copied().max().unwrap_or(0),
+            unreachable: nodes - depths.len(),
+        }
+    }
+}
+
+/// Return the distance in links from `start` to every node reachable from it.
+pub fn breadth_first_depths(graph: &TopologyGraph, start: HostIndex) -> HashMap<HostIndex, usize> {
+    let mut depths = HashMap::new();
+    if graph.node_weight(start).is_none() {
+        return depths;
+    }
+    depths.insert(start, 0);
+    let mut queue = VecDeque::from([start]);
+    while let Some(node_index) = queue.pop_front() {
+        let depth = depths[&node_index];
+        for neighbor in graph.neighbors(node_index) {
+            if !depths.contains_key(&neighbor) {
+                depths.insert(neighbor, depth + 1);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    depths
+}
+
+impl fmt::Display for ScanStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Hosts up: {}", self.hosts_up)?;
+        writeln!(f, "Hosts down: {}", self.hosts_down)?;
+        writeln!(f, "Routers: {}", self.routers)?;
+
+        writeln!(f, "Traceroute hops:")?;
+        for (hops, hosts) in &self.hop_counts {
+            match hops {
+                0 => writeln!(f, "  no traceroute: {hosts}")?,
+                _ => writeln!(f, "  {hops}: {hosts}")?,
+            }
+        }
+
+        writeln!(f, "OS families:")?;
+        for (family, hosts) in &self.os_families {
+            writeln!(f, "  {family}: {hosts}")?;
+        }
+
+        writeln!(f, "Most common open ports:")?;
+        for port in &self.top_ports {
+            match &port.service {
+                Some(service) => writeln!(f, "  {} {service}: {}", port.port, port.hosts)?,
+                None => writeln!(f, "  {}: {}", port.port, port.hosts)?,
+            }
+        }
+
+        let graph = &self.graph;
+        writeln!(f, "Graph:")?;
+        writeln!(f, "  nodes: {}", graph.nodes)?;
+        writeln!(f, "  edges: {}", graph.edges)?;
+        writeln!(f, "  average degree: {:.2}", graph.average_degree)?;
+        writeln!(f, "  max degree: {}", graph.max_degree)?;
+        writeln!(f, "  components: {}", graph.components)?;
+        writeln!(f, "  max depth from scanner: {}", graph.max_depth)?;
+        writeln!(f, "  unreachable from scanner: {}", graph.unreachable)
+    }
+}
+
+/// Run `neuromaster stats [--json] [files]`: load the inputs without opening a window and print its summary.
+pub fn run(args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut json = false;
+    let mut inputs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let loaded = import::load_inputs(&inputs, stream::report_progress)?;
+    stream::end_progress();
+    let stats = ScanStats::from_loaded(&loaded);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print!("{stats}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two hosts behind a gateway, and a pair of untraced hosts linked only to each other.
+    fn topology() -> Topology {
+        import::import_json(
+            br#"{
+                "hosts": [
+                    {
+                        "address": "10.0.0.2",
+                        "os": "Linux 5.4",
+                        "ports": [{ "portid": 22, "service": "ssh" }, { "portid": 80 }],
+                        "trace": [{ "address": "10.0.0.1" }, { "address": "10.0.0.2" }]
+                    },
+                    {
+                        "address": "10.0.0.3",
+                        "ports": [{ "portid": 22 }, { "portid": 443, "state": "closed" }],
+                        "trace": [{ "address": "10.0.0.1" }, { "address": "10.0.0.3" }]
+                    },
+                    { "address": "10.0.1.5", "os": "FreeBSD 13.2" },
+                    { "address": "10.0.1.6" }
+                ],
+                "links": [{ "source": "10.0.1.5", "target": "10.0.1.6" }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn scan_stats_count_hosts_routes_and_ports() {
+        let stats = ScanStats::new(&topology(), 3);
+        assert_eq!(stats.hosts_up, 4);
+        assert_eq!(stats.hosts_down, 3);
+        assert_eq!(stats.routers, 1);
+        assert_eq!(stats.hop_counts, BTreeMap::from([(0, 2), (2, 2)]));
+        assert_eq!(
+            stats.os_families,
+            BTreeMap::from([
+                ("FreeBSD".to_string(), 1),
+                ("Linux".to_string(), 1),
+                ("unknown".to_string(), 2),
+            ])
+        );
+
+        let ports: Vec<(&str, Option<&str>, usize)> = stats
+            .top_ports
+            .iter()
+            .map(|port| (port.port.as_str(), port.service.as_deref(), port.hosts))
+            .collect();
+        assert_eq!(ports, [("22/tcp", Some("ssh"), 2), ("80/tcp", None, 1)]);
+
+        let summary = stats.to_string();
+        assert!(summary.contains("  no traceroute: 2\n"));
+        assert!(summary.contains("  22/tcp ssh: 2\n"));
+    }
+
+    #[test]
+    fn graph_metrics_measure_from_the_scanner() {
+        let metrics = GraphMetrics::from_topology(&topology());
+        assert_eq!(metrics.nodes, 6);
+        assert_eq!(metrics.edges, 4);
+        assert!((metrics.average_degree - 8.0 / 6.0).abs() < 1e-6);
+        assert_eq!(metrics.max_degree, 3);
+        assert_eq!(metrics.components, 2);
+        assert_eq!(metrics.max_depth, 2);
+        assert_eq!(metrics.unreachable, 2);
+    }
+
+    #[test]
+    fn breadth_first_depths_ignore_missing_starts() {
+        let topology = topology();
+        let depths = breadth_first_depths(&topology.graph, Topology::root());
+        let depth = |addr: &str| depths[&topology.find_host(addr.parse().unwrap()).unwrap()];
+        assert_eq!(depth("10.0.0.1"), 1);
+        assert_eq!(depth("10.0.0.3"), 2);
+        assert!(!depths.contains_key(&topology.find_host("10.0.1.5".parse().unwrap()).unwrap()));
+
+        assert!(breadth_first_depths(&topology.graph, HostIndex::new(99)).is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+//! Event-based loading of nmap XML, one `<host>` element at a time, so multi-gigabyte scans load
+//! with memory bounded by the topology rather than the document.
+
+use crate::model::{self, SimpleHost};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use xml::reader::{EventReader, XmlEvent};
+use xml::writer::EmitterConfig;
+
+/// One host element read from the scan.
+pub enum ScanEvent {
+    Host(SimpleHost),
+    HostDown,
+}
+
+/// How far through its input a load has got.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+    pub bytes_read: u64,
+    /// Unknown for pipes and other inputs without a size.
+    pub total_bytes: Option<u64>,
+    pub hosts_up: usize,
+    pub hosts_down: usize,
+}
+
+impl Progress {
+    pub fn fraction(&self) -> Option<f32> {
+        self.total_bytes
+            .filter(|total| *total > 0)
+            .map(|total| (self.bytes_read as f32 / total as f32).min(1.0))
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(fraction) = self.fraction() {
+            write!(f, "{:.0}%, ", fraction * 100.0)?;
+        }
+        write!(f, "{} hosts up, {} down", self.hosts_up, self.hosts_down)
+    }
+}
+
+/// Counts the bytes passing through a reader, for progress reports from another thread.
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+// How often a followed file is checked for more output.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+// The end of an nmap XML document, after which a followed file has nothing more to give.
+const DOCUMENT_END: &[u8] = b"</nmaprun>";
+
+/// Reads a file another process is still writing, waiting at the end of it for more instead of stopping.
+/// Reading ends once nmap has closed the document.
+pub struct FollowReader {
+    file: File,
+    /// The last bytes read, so the end tag is found even when it is split across reads.
+    tail: Vec<u8>,
+    finished: bool,
+}
+
+impl FollowReader {
+    /// Open the file, waiting for it to be created if the scan has not started writing it yet.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        loop {
+            match File::open(path) {
+                Ok(file) => {
+                    return Ok(FollowReader {
+                        file,
+                        tail: Vec::new(),
+                        finished: false,
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => thread::sleep(FOLLOW_INTERVAL),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Read for FollowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.finished {
+            return Ok(0);
+        }
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                self.tail.extend_from_slice(&buf[..read]);
+                if self
+                    .tail
+                    .windows(DOCUMENT_END.len())
+                    .any(|window| window == DOCUMENT_END)
+                {
+                    self.finished = true;
+                }
+                let keep = self.tail.len().saturating_sub(DOCUMENT_END.len() - 1);
+                self.tail.drain(..keep);
+                return Ok(read);
+            }
+            thread::sleep(FOLLOW_INTERVAL);
+        }
+    }
+}
+
+/// Iterates over the hosts of an nmap XML document as they are read.
+pub struct HostStream<R: Read> {
+    events: EventReader<CountingReader<R>>,
+    bytes_read: Arc<AtomicU64>,
+    progress: Progress,
+    /// How many elements outside of hosts are open, so the stream ends with the root element.
+    depth: usize,
+    finished: bool,
+}
+
+impl<R: Read> HostStream<R> {
+    pub fn new(reader: R, total_bytes: Option<u64>) -> Self {
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        HostStream {
+            events: EventReader::new(CountingReader {
+                inner: reader,
+                count: bytes_read.clone(),
+            }),
+            bytes_read,
+            progress: Progress {
+                total_bytes,
+                ..Progress::default()
+            },
+            depth: 0,
+            finished: false,
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            ..self.progress
+        }
+    }
+
+    /// Copy the host element just opened into a standalone document and parse it with rust_nmap.
+    fn read_host(
+        &mut self,
+        start: &XmlEvent,
+    ) -> Result<Option<ScanEvent>, Box<dyn std::error::Error>> {
+        let mut buffer = Vec::new();
+        let mut writer = EmitterConfig::new()
+            .write_document_declaration(false)
+            .create_writer(&mut buffer);
+        writer.write(xml::writer::XmlEvent::start_element("nmaprun"))?;
+        if let Some(event) = start.as_writer_event() {
+            writer.write(event)?;
+        }
+
+        let mut depth = 1;
+        while depth > 0 {
+            let event = self.events.next()?;
+            match &event {
+                XmlEvent::StartElement { .. } => depth += 1,
+                XmlEvent::EndElement { .. } => depth -= 1,
+                XmlEvent::EndDocument => return Err("Scan ended inside a host element.".into()),
+                _ => {}
+            }
+            if let Some(event) = event.as_writer_event() {
+                writer.write(event)?;
+            }
+        }
+        writer.write(xml::writer::XmlEvent::end_element())?;
+        drop(writer);
+
+        let scan = rust_nmap::parse_nmap_xml_bytes(&buffer)?;
+        let host = match (|| scan.host.as_ref()?.first())() {
+            None => return Ok(None),
+            Some(host) => host,
+        };
+        if model::is_host_down(host) {
+            self.progress.hosts_down += 1;
+            return Ok(Some(ScanEvent::HostDown));
+        }
+        match SimpleHost::from_fullhost(host) {
+            Ok(host) => {
+                self.progress.hosts_up += 1;
+                Ok(Some(ScanEvent::Host(host)))
+            }
+            // Hosts without a usable address are left out, as build_topology does.
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl<R: Read> Iterator for HostStream<R> {
+    type Item = Result<ScanEvent, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let event = match self.events.next() {
+                Ok(event) => event,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            match &event {
+                XmlEvent::StartElement { name, .. } if name.local_name == "host" => {
+                    match self.read_host(&event) {
+                        Ok(None) => continue,
+                        Ok(Some(scan_event)) => return Some(Ok(scan_event)),
+                        Err(e) => {
+                            self.finished = true;
+                            return Some(Err(e));
+                        }
+                    }
+                }
+                XmlEvent::StartElement { .. } => self.depth += 1,
+                // A followed file only reaches its end once nmap has finished, but the document is complete
+                // as soon as the root element closes.
+                XmlEvent::EndElement { .. } => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if self.depth == 0 {
+                        self.finished = true;
+                    }
+                }
+                XmlEvent::EndDocument => self.finished = true,
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Messages from a load running on a background thread.
+pub enum LoadMessage {
+    Host(SimpleHost),
+    Progress(Progress),
+    /// The load is over, with an error message if it failed.
+    Finished(Option<String>),
+}
+
+/// Stream hosts from the reader on a new thread, reporting progress after every host.
+pub fn spawn_loader<R: Read + Send + 'static>(
+    reader: R,
+    total_bytes: Option<u64>,
+) -> Receiver<LoadMessage> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || send_hosts(HostStream::new(reader, total_bytes), &sender));
+    receiver
+}
+
+/// Follow an nmap scan as it writes its XML output, sending each host once its element is complete.
+/// The load finishes when nmap closes the document.
+pub fn spawn_follower(path: PathBuf) -> Receiver<LoadMessage> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || match FollowReader::open(&path) {
+        Ok(reader) => send_hosts(HostStream::new(reader, None), &sender),
+        Err(e) => {
+            let _ = sender.send(LoadMessage::Finished(Some(e.to_string())));
+        }
+    });
+    receiver
+}
+
+fn send_hosts<R: Read>(mut stream: HostStream<R>, sender: &mpsc::Sender<LoadMessage>) {
+    let mut error = None;
+    while let Some(event) = stream.next() {
+        match event {
+            Ok(ScanEvent::Host(host)) => {
+                if sender.send(LoadMessage::Host(host)).is_err() {
+                    return;
+                }
+            }
+            Ok(ScanEvent::HostDown) => {}
+            Err(e) => error = Some(e.to_string()),
+        }
+        let _ = sender.send(LoadMessage::Progress(stream.progress()));
+    }
+    let _ = sender.send(LoadMessage::Progress(stream.progress()));
+    let _ = sender.send(LoadMessage::Finished(error));
+}
+
+/// Show progress on stderr, overwriting the previous report, when stderr is a terminal.
+pub fn report_progress(progress: &Progress) {
+    let mut stderr = io::stderr();
+    if stderr.is_terminal() {
+        let _ = write!(stderr, "\rLoading: {progress}\x1b[K");
+        let _ = stderr.flush();
+    }
+}
+
+/// End the line report_progress has been overwriting.
+pub fn end_progress() {
+    let mut stderr = io::stderr();
+    if stderr.is_terminal() {
+        let _ = writeln!(stderr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SCAN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" args="nmap 10.0.0.0/30" start="1700000000" version="7.94" xmloutputversion="1.05">
+<host><status state="up" reason="syn-ack" reason_ttl="64"/>
+<address addr="10.0.0.1" addrtype="ipv4"/>
+</host>
+<host><status state="down" reason="no-response" reason_ttl="0"/>
+<address addr="10.0.0.2" addrtype="ipv4"/>
+</host>
+<host><status state="up" reason="syn-ack" reason_ttl="64"/>
+<address addr="10.0.0.3" addrtype="ipv4"/>
+</host>
+<runstats><finished time="1700000010" elapsed="10.00" exit="success"/><hosts up="2" down="1" total="3"/>
+</runstats>
+</nmaprun>
+"#;
+
+    fn addresses(events: Vec<ScanEvent>) -> Vec<String> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                ScanEvent::Host(host) => Some(host.main_addr.to_string()),
+                ScanEvent::HostDown => None,
+            })
+            .collect()
+    }
+
+    /// A scratch file for following, removed along with its directory when dropped.
+    struct ScratchFile(PathBuf);
+
+    impl ScratchFile {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("neuromaster-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            ScratchFile(dir.join("scan.xml"))
+        }
+    }
+
+    impl Drop for ScratchFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn host_streams_stop_at_the_end_of_the_document() {
+        // Whatever follows the root element, such as a second run appended to the file, is not read.
+        let input = format!("{SCAN}<nmaprun><host>");
+        let mut stream = HostStream::new(input.as_bytes(), Some(input.len() as u64));
+        let events: Vec<ScanEvent> = stream.by_ref().map(Result::unwrap).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(addresses(events), ["10.0.0.1", "10.0.0.3"]);
+
+        let progress = stream.progress();
+        assert_eq!((progress.hosts_up, progress.hosts_down), (2, 1));
+        assert!(progress.bytes_read >= SCAN.trim_end().len() as u64);
+    }
+
+    #[test]
+    fn host_streams_end_with_an_error_on_partial_documents() {
+        let cut = SCAN.find("<address addr=\"10.0.0.3\"").unwrap();
+        let mut stream = HostStream::new(&SCAN.as_bytes()[..cut], None);
+        assert!(matches!(stream.next(), Some(Ok(ScanEvent::Host(_)))));
+        assert!(matches!(stream.next(), Some(Ok(ScanEvent::HostDown))));
+        assert!(matches!(stream.next(), Some(Err(_))));
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn follow_readers_stop_after_the_end_tag() {
+        let file = ScratchFile::new("follow-end");
+        fs::write(&file.0, SCAN).unwrap();
+
+        // Small reads split the end tag, which must still be found for the read to end.
+        let mut reader = FollowReader::open(&file.0).unwrap();
+        let mut read = Vec::new();
+        let mut buffer = [0; 4];
+        loop {
+            match reader.read(&mut buffer).unwrap() {
+                0 => break,
+                n => read.extend_from_slice(&buffer[..n]),
+            }
+        }
+        assert_eq!(String::from_utf8(read).unwrap().trim_end(), SCAN.trim_end());
+        assert_eq!(reader.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn followers_wait_for_the_rest_of_a_partial_document() {
+        let file = ScratchFile::new("follow-partial");
+        let cut = SCAN[..SCAN.find("10.0.0.3").unwrap()]
+            .rfind("<host>")
+            .unwrap();
+        fs::write(&file.0, &SCAN[..cut]).unwrap();
+
+        let messages = spawn_follower(file.0.clone());
+        let first = messages.recv().unwrap();
+        assert!(
+            matches!(&first, LoadMessage::Host(host) if host.main_addr.to_string() == "10.0.0.1")
+        );
+
+        // nmap writes the rest of the scan later.
+        thread::sleep(FOLLOW_INTERVAL * 2);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&file.0)
+            .unwrap()
+            .write_all(SCAN[cut..].as_bytes())
+            .unwrap();
+
+        let mut hosts = Vec::new();
+        let mut finished = None;
+        for message in messages {
+            match message {
+                LoadMessage::Host(host) => hosts.push(host.main_addr.to_string()),
+                LoadMessage::Progress(_) => {}
+                LoadMessage::Finished(error) => finished = Some(error),
+            }
+        }
+        assert_eq!(hosts, ["10.0.0.3"]);
+        assert_eq!(finished, Some(None));
+    }
+}
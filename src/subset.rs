@@ -0,0 +1,248 @@
+//! Writing a reduced copy of an nmap XML scan that holds only chosen hosts, with everything nmap recorded
+//! about them (ports, OS matches, script output, traces) copied through untouched.
+
+use crate::import::{self, InputFormat};
+use crate::model::SimpleHost;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use xml::reader::{EventReader, XmlEvent};
+use xml::writer::EmitterConfig;
+
+/// Parse a host element on its own, wrapped in an `<nmaprun>` as rust_nmap expects.
+fn parse_host(events: &[XmlEvent]) -> Result<Option<SimpleHost>, Box<dyn std::error::Error>> {
+    let mut buffer = Vec::new();
+    let mut writer = EmitterConfig::new()
+        .write_document_declaration(false)
+        .create_writer(&mut buffer);
+    writer.write(xml::writer::XmlEvent::start_element("nmaprun"))?;
+    for event in events {
+        if let Some(event) = event.as_writer_event() {
+            writer.write(event)?;
+        }
+    }
+    writer.write(xml::writer::XmlEvent::end_element())?;
+    drop(writer);
+
+    let scan = rust_nmap::parse_nmap_xml_bytes(&buffer)?;
+    // Hosts that were down, or have no usable address, have nothing to filter on.
+    Ok((|| scan.host.as_ref()?.first())().and_then(|host| SimpleHost::from_fullhost(host).ok()))
+}
+
+/// Copy the scan, leaving out every host `keep` rejects and the hosts that were down, and return how many
+/// hosts were kept. The run statistics are updated to count only the kept hosts.
+pub fn write_subset(
+    reader: impl Read,
+    writer: impl Write,
+    mut keep: impl FnMut(&SimpleHost) -> bool,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut events = EventReader::new(reader);
+    let mut output = EmitterConfig::new().create_writer(writer);
+    let mut kept = 0;
+
+    loop {
+        let event = events.next()?;
+        let is_host =
+            matches!(&event, XmlEvent::StartElement { name, .. } if name.local_name == "host");
+        if is_host {
+            let mut host_events = vec![event];
+            let mut depth = 1;
+            while depth > 0 {
+                let event = events.next()?;
+                match &event {
+                    XmlEvent::StartElement { .. } => depth += 1,
+                    XmlEvent::EndElement { .. } => depth -= 1,
+                    XmlEvent::EndDocument => return Err("Scan ended inside a host element.".into()),
+                    _ => {}
+                }
+                host_events.push(event);
+            }
+            if parse_host(&host_events)?.is_some_and(|host| keep(&host)) {
+                for event in &host_events {
+                    if let Some(event) = event.as_writer_event() {
+                        output.write(event)?;
+                    }
+                }
+                kept += 1;
+            }
+            continue;
+        }
+        match &event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } if name.local_name == "hosts" => {
+                // Run statistics come after the hosts, so the kept count is final by now.
+                let counts: Vec<(String, String)> = attributes
+                    .iter()
+                    .map(|attribute| {
+                        let value = match attribute.name.local_name.as_str() {
+                            "up" | "total" => kept.to_string(),
+                            "down" => "0".to_string(),
+                            _ => attribute.value.clone(),
+                        };
+                        (attribute.name.local_name.clone(), value)
+                    })
+                    .collect();
+                let mut element = xml::writer::XmlEvent::start_element("hosts");
+                for (name, value) in &counts {
+                    element = element.attr(name.as_str(), value);
+                }
+                output.write(element)?;
+                continue;
+            }
+            XmlEvent::EndDocument => break,
+            _ => {}
+        }
+        if let Some(event) = event.as_writer_event() {
+            output.write(event)?;
+        }
+    }
+    output.into_inner().flush()?;
+
+    Ok(kept)
+}
+
+/// Hosts chosen on the command line. Each kind of criterion given must match; within a kind, any value may.
+#[derive(Default)]
+pub(crate) struct Criteria {
+    pub hosts: Vec<IpAddr>,
+    pub ports: Vec<u16>,
+    pub os: Vec<String>,
+}
+
+impl Criteria {
+    pub fn matches(&self, host: &SimpleHost) -> bool {
+        (self.hosts.is_empty() || self.hosts.contains(&host.main_addr))
+            && (self.ports.is_empty()
+                || host
+                    .open_ports()
+                    .any(|port| self.ports.contains(&port.portid)))
+            && (self.os.is_empty()
+                || host.os_guess.as_ref().is_some_and(|os| {
+                    let name = os.name().to_lowercase();
+                    self.os.iter().any(|os| name.contains(&os.to_lowercase()))
+                }))
+    }
+}
+
+/// Entry point for `neuromaster subset`.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut input = None;
+    let mut output = None;
+    let mut criteria = Criteria::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} requires a value"));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value(&arg)?)),
+            "--host" => criteria.hosts.push(value(&arg)?.parse()?),
+            "--port" => criteria.ports.push(value(&arg)?.parse()?),
+            "--os" => criteria.os.push(value(&arg)?),
+            _ => input = Some(PathBuf::from(arg)),
+        }
+    }
+    let output = output.ok_or("subset requires an output file: -o <file.xml>")?;
+
+    let input = import::open_input(input.as_deref())?;
+    if input.format != InputFormat::NmapXml {
+        return Err("subset can only copy hosts out of nmap XML scans".into());
+    }
+    let writer = BufWriter::new(File::create(&output)?);
+    let kept = write_subset(input.reader, writer, |host| criteria.matches(host))?;
+    eprintln!("Wrote {kept} hosts to {}", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCAN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" args="nmap -sV 10.0.0.0/30" start="1700000000" version="7.94" xmloutputversion="1.05">
+<host><status state="up" reason="syn-ack" reason_ttl="64"/>
+<address addr="10.0.0.1" addrtype="ipv4"/>
+<ports><port protocol="tcp" portid="22"><state state="open" reason="syn-ack" reason_ttl="64"/><service name="ssh" product="OpenSSH" version="8.9p1"/></port></ports>
+<os><osmatch name="Linux 5.4" accuracy="98" line="1"/></os>
+</host>
+<host><status state="up" reason="syn-ack" reason_ttl="64"/>
+<address addr="10.0.0.2" addrtype="ipv4"/>
+<ports><port protocol="tcp" portid="80"><state state="open" reason="syn-ack" reason_ttl="64"/><service name="http"/></port></ports>
+</host>
+<host><status state="down" reason="no-response" reason_ttl="0"/>
+<address addr="10.0.0.3" addrtype="ipv4"/>
+</host>
+<runstats><finished time="1700000010" elapsed="10.00" exit="success"/><hosts up="2" down="2" total="4"/>
+</runstats>
+</nmaprun>
+"#;
+
+    #[test]
+    fn subsets_parse_back_as_nmap_scans() {
+        let mut output = Vec::new();
+        let kept = write_subset(SCAN.as_bytes(), &mut output, |host| {
+            host.main_addr == "10.0.0.2".parse::<IpAddr>().unwrap()
+        })
+        .unwrap();
+        assert_eq!(kept, 1);
+
+        let scan = rust_nmap::parse_nmap_xml_bytes(&output).unwrap();
+        let hosts = scan.host.unwrap();
+        assert_eq!(hosts.len(), 1);
+        let host = SimpleHost::from_fullhost(&hosts[0]).unwrap();
+        assert_eq!(host.main_addr, "10.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!(host.ports[0].portid, 80);
+
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.contains(r#"<hosts up="1" down="0" total="1""#));
+    }
+
+    #[test]
+    fn subsets_keep_nothing_but_the_run() {
+        let mut output = Vec::new();
+        assert_eq!(
+            write_subset(SCAN.as_bytes(), &mut output, |_| false).unwrap(),
+            0
+        );
+        let text = String::from_utf8(output).unwrap();
+        assert!(!text.contains("<host>"));
+        assert!(text.contains(r#"<hosts up="0" down="0" total="0""#));
+        assert!(text.contains("<runstats>"));
+    }
+
+    #[test]
+    fn criteria_must_all_match() {
+        let scan = rust_nmap::parse_nmap_xml_bytes(SCAN.as_bytes()).unwrap();
+        let hosts: Vec<SimpleHost> = scan
+            .host
+            .unwrap()
+            .iter()
+            .filter_map(|host| SimpleHost::from_fullhost(host).ok())
+            .collect();
+        let chosen = |criteria: &Criteria| -> Vec<String> {
+            hosts
+                .iter()
+                .filter(|host| criteria.matches(host))
+                .map(|host| host.main_addr.to_string())
+                .collect()
+        };
+
+        assert_eq!(chosen(&Criteria::default()).len(), hosts.len());
+        let by_port = Criteria {
+            ports: vec![80, 22],
+            ..Criteria::default()
+        };
+        assert_eq!(chosen(&by_port), ["10.0.0.1", "10.0.0.2"]);
+        let by_host_and_port = Criteria {
+            hosts: vec!["10.0.0.2".parse().unwrap(), "10.0.0.3".parse().unwrap()],
+            ports: vec![22],
+            ..Criteria::default()
+        };
+        assert!(chosen(&by_host_and_port).is_empty());
+        let by_os = Criteria {
+            os: vec!["linux".to_string(), "windows".to_string()],
+            ..Criteria::default()
+        };
+        assert_eq!(chosen(&by_os), ["10.0.0.1"]);
+    }
+}
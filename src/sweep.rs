@@ -0,0 +1,349 @@
+//! Loading results from fast port sweepers: masscan's XML, JSON and list output, zmap CSV and RustScan.
+//!
+//! Sweepers report open ports but no traceroutes or OS guesses, so their hosts are merged with nmap
+//! results for the same addresses when loaded together, and otherwise hang off the scanner.
+
+use crate::import;
+use crate::model::{SimpleHost, SimplePort};
+use crate::topology::Topology;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use xml::reader::{EventReader, XmlEvent};
+
+#[derive(Deserialize)]
+struct MasscanRecord {
+    /// Missing from the `{"finished": 1}` marker older versions end with.
+    ip: Option<IpAddr>,
+    #[serde(default)]
+    ports: Vec<MasscanPort>,
+}
+
+#[derive(Deserialize)]
+struct MasscanPort {
+    port: u16,
+    #[serde(default = "import::default_protocol")]
+    proto: String,
+    #[serde(default = "import::default_state")]
+    status: String,
+    service: Option<MasscanService>,
+}
+
+#[derive(Deserialize)]
+struct MasscanService {
+    name: Option<String>,
+    banner: Option<String>,
+}
+
+fn swept_host(addr: IpAddr, ports: Vec<SimplePort>) -> SimpleHost {
+    let mut host = SimpleHost::new(addr);
+    host.scanned = true;
+    host.ports = ports;
+    host
+}
+
+fn open_port(protocol: &str, portid: u16) -> SimplePort {
+    SimplePort {
+        protocol: protocol.to_string(),
+        portid,
+        state: "open".to_string(),
+        service: None,
+        product: None,
+        version: None,
+    }
+}
+
+/// Build the topology from masscan's `-oX` output, which resembles nmap's but reports one port per host element.
+pub fn import_masscan_xml(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let mut topology = Topology::new()?;
+    let mut current: Option<SimpleHost> = None;
+
+    for event in EventReader::new(bytes) {
+        match event? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|attribute| attribute.name.local_name == key)
+                        .map(|attribute| attribute.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "address" => {
+                        current = attribute("addr")
+                            .and_then(|addr| addr.parse().ok())
+                            .map(|addr| swept_host(addr, Vec::new()));
+                    }
+                    "port" => {
+                        let portid = attribute("portid").and_then(|portid| portid.parse().ok());
+                        if let (Some(host), Some(portid)) = (current.as_mut(), portid) {
+                            let protocol =
+                                attribute("protocol").unwrap_or_else(import::default_protocol);
+                            host.ports.push(open_port(&protocol, portid));
+                        }
+                    }
+                    "state" => {
+                        if let Some(port) = current.as_mut().and_then(|host| host.ports.last_mut())
+                        {
+                            if let Some(state) = attribute("state") {
+                                port.state = state;
+                            }
+                        }
+                    }
+                    "service" => {
+                        if let Some(port) = current.as_mut().and_then(|host| host.ports.last_mut())
+                        {
+                            port.service = attribute("name");
+                            port.product = attribute("banner");
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::EndElement { name } if name.local_name == "host" => {
+                // Hosts repeat once per port, and merge by address.
+                if let Some(host) = current.take() {
+                    topology.add_scanned_host(host);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(topology)
+}
+
+/// Build the topology from masscan's `-oJ` or `-oD` output.
+pub fn import_masscan_json(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let records: Vec<MasscanRecord> = match serde_json::from_slice(bytes) {
+        Ok(records) => records,
+        // Older versions leave a comma before the closing bracket and -oD writes bare records,
+        // but both put one record on each line.
+        Err(_) => String::from_utf8_lossy(bytes)
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| line.starts_with('{'))
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut topology = Topology::new()?;
+    for record in records {
+        let addr = match record.ip {
+            None => continue,
+            Some(addr) => addr,
+        };
+        let ports = record
+            .ports
+            .into_iter()
+            .map(|port| {
+                let service = port.service.unwrap_or(MasscanService {
+                    name: None,
+                    banner: None,
+                });
+                SimplePort {
+                    protocol: port.proto,
+                    portid: port.port,
+                    state: port.status,
+                    service: service.name,
+                    product: service.banner,
+                    version: None,
+                }
+            })
+            .collect();
+        topology.add_scanned_host(swept_host(addr, ports));
+    }
+
+    Ok(topology)
+}
+
+/// Build the topology from masscan's `-oL` output, e.g. `open tcp 80 10.0.0.1 1600000000`.
+/// Banner lines add the service and banner to their port.
+pub fn import_masscan_list(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut topology = Topology::new()?;
+
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (kind, protocol, portid, addr) = match words[..] {
+            [kind, protocol, portid, addr, ..] if !kind.starts_with('#') => {
+                (kind, protocol, portid, addr)
+            }
+            _ => continue,
+        };
+        let mut port = open_port(protocol, portid.parse()?);
+        match kind {
+            "open" => {}
+            "banner" => {
+                port.service = words.get(5).map(|service| service.to_string());
+                port.product =
+                    Some(words[6.min(words.len())..].join(" ")).filter(|banner| !banner.is_empty());
+            }
+            state => port.state = state.to_string(),
+        }
+        topology.add_scanned_host(swept_host(addr.parse()?, vec![port]));
+    }
+
+    Ok(topology)
+}
+
+/// Build the topology from zmap output: a bare list of addresses, or CSV with a header naming its fields.
+/// `saddr` is required; `sport`, `classification` and `success` are read when present.
+pub fn import_zmap(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let first_field = lines
+        .peek()
+        .copied()
+        .and_then(|line| line.split(',').next())
+        .unwrap_or_default();
+    let columns: Vec<String> = if first_field.parse::<IpAddr>().is_ok() {
+        vec!["saddr".to_string()]
+    } else {
+        lines
+            .next()
+            .unwrap_or_default()
+            .split(',')
+            .map(|column| column.trim().to_string())
+            .collect()
+    };
+    let column = |name: &str| columns.iter().position(|column| column == name);
+    let saddr = column("saddr").ok_or("zmap output has no saddr column.")?;
+    let sport_column = column("sport");
+    let classification_column = column("classification");
+    let success_column = column("success");
+
+    let mut topology = Topology::new()?;
+    for line in lines {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |index: Option<usize>| index.and_then(|index| fields.get(index).copied());
+        if matches!(field(success_column), Some("0" | "false")) {
+            continue;
+        }
+        let addr = field(Some(saddr))
+            .ok_or(format!("zmap row has no address: {line}"))?
+            .parse()?;
+        let classification = field(classification_column).unwrap_or_default();
+        let ports = field(sport_column)
+            .and_then(|sport| sport.parse().ok())
+            .map(|portid| {
+                let protocol = if classification.starts_with("udp") {
+                    "udp"
+                } else {
+                    "tcp"
+                };
+                let mut port = open_port(protocol, portid);
+                if classification == "rst" {
+                    port.state = "closed".to_string();
+                }
+                port
+            })
+            .into_iter()
+            .collect();
+        topology.add_scanned_host(swept_host(addr, ports));
+    }
+
+    Ok(topology)
+}
+
+/// Build the topology from RustScan's greppable output (`10.0.0.1 -> [22,80]`) or the `Open 10.0.0.1:22`
+/// lines of its normal output.
+pub fn import_rustscan(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut topology = Topology::new()?;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some((addr, ports)) = line.split_once(" -> [") {
+            let ports = ports
+                .trim_end_matches(']')
+                .split(',')
+                .filter_map(|portid| portid.trim().parse().ok())
+                .map(|portid| open_port("tcp", portid))
+                .collect();
+            topology.add_scanned_host(swept_host(addr.trim().parse()?, ports));
+        } else if let Some(socket) = line.strip_prefix("Open ") {
+            let socket: SocketAddr = socket.trim().parse()?;
+            topology.add_scanned_host(swept_host(
+                socket.ip(),
+                vec![open_port("tcp", socket.port())],
+            ));
+        }
+    }
+
+    Ok(topology)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::InputFormat;
+    use std::path::Path;
+
+    fn host<'a>(topology: &'a Topology, addr: &str) -> &'a SimpleHost {
+        &topology.graph[topology.find_host(addr.parse().unwrap()).unwrap()]
+    }
+
+    #[test]
+    fn masscan_list_banners_fill_in_their_port() {
+        let output = "#masscan\n\
+            open tcp 80 10.0.0.1 1700000000\n\
+            banner tcp 80 10.0.0.1 1700000001 http HTTP/1.1 200 OK\n\
+            open tcp 22 10.0.0.2 1700000000\n\
+            # end\n";
+        assert_eq!(
+            InputFormat::detect(None, output.as_bytes()),
+            InputFormat::MasscanList
+        );
+        let topology = import_masscan_list(output.as_bytes()).unwrap();
+
+        let web = host(&topology, "10.0.0.1");
+        assert_eq!(web.ports.len(), 1);
+        assert_eq!(web.ports[0].state, "open");
+        assert_eq!(web.ports[0].service.as_deref(), Some("http"));
+        assert_eq!(web.ports[0].product.as_deref(), Some("HTTP/1.1 200 OK"));
+        assert_eq!(host(&topology, "10.0.0.2").ports[0].portid, 22);
+    }
+
+    #[test]
+    fn zmap_csv_columns_follow_the_header() {
+        let output = "classification,saddr,sport,success\n\
+            synack,10.0.0.1,443,1\n\
+            rst,10.0.0.2,443,1\n\
+            synack,10.0.0.3,443,0\n";
+        let topology = import_zmap(output.as_bytes()).unwrap();
+
+        let open = host(&topology, "10.0.0.1");
+        assert_eq!(open.ports[0].portid, 443);
+        assert_eq!(open.ports[0].protocol, "tcp");
+        assert_eq!(open.ports[0].state, "open");
+        assert_eq!(host(&topology, "10.0.0.2").ports[0].state, "closed");
+        // Unsuccessful probes are left out.
+        assert!(topology.find_host("10.0.0.3".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn zmap_address_lists_need_no_header() {
+        let topology = import_zmap(b"10.0.0.1\n10.0.0.2\n").unwrap();
+        assert!(host(&topology, "10.0.0.1").scanned);
+        assert!(host(&topology, "10.0.0.2").ports.is_empty());
+    }
+
+    #[test]
+    fn masscan_json_is_recognised_by_content() {
+        let output = "[\n\
+            {   \"ip\": \"10.0.0.1\",   \"timestamp\": \"1700000000\", \"ports\": [ {\"port\": 80, \"proto\": \"tcp\", \"status\": \"open\", \"reason\": \"syn-ack\", \"ttl\": 64} ] },\n\
+            ]\n";
+        assert_eq!(
+            InputFormat::detect(Some(Path::new("scan.json")), output.as_bytes()),
+            InputFormat::MasscanJson
+        );
+        let topology = import_masscan_json(output.as_bytes()).unwrap();
+        assert_eq!(host(&topology, "10.0.0.1").ports[0].portid, 80);
+    }
+}
@@ -0,0 +1,455 @@
+use crate::model::{SimpleHop, SimpleHost, SimpleLink};
+use petgraph::stable_graph::{EdgeIndex, NodeIndex, StableGraph};
+use petgraph::Undirected;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+pub type HostIndex = NodeIndex;
+pub type LinkIndex = EdgeIndex;
+pub type TopologyGraph = StableGraph<SimpleHost, SimpleLink, Undirected>;
+
+/// Hosts, the links traceroutes found between them, and groups of hosts, independent of any layout.
+#[derive(Clone, Debug)]
+pub struct Topology {
+    pub graph: TopologyGraph,
+    addresses: HashMap<IpAddr, HostIndex>,
+    /// Groups keyed by the host standing in for them.
+    groups: HashMap<HostIndex, Vec<HostIndex>>,
+    grouped_into: HashMap<HostIndex, HostIndex>,
+}
+
+impl Topology {
+    /// Start a topology holding only the scanner.
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut topology = Self {
+            graph: TopologyGraph::default(),
+            addresses: HashMap::new(),
+            groups: HashMap::new(),
+            grouped_into: HashMap::new(),
+        };
+
+        let mut localhost = SimpleHost::from_strs("127.0.0.1", "localhost")?;
+        SimpleHost::set_rtt(&mut localhost, 0.0);
+        topology.add_host(localhost);
+        Ok(topology)
+    }
+
+    /// The scanner itself, which every traceroute starts from, is always the first host inserted.
+    pub fn root() -> HostIndex {
+        NodeIndex::new(0)
+    }
+
+    pub fn host_indices(&self) -> impl Iterator<Item = HostIndex> + '_ {
+        self.graph.node_indices()
+    }
+
+    pub fn hosts(&self) -> impl Iterator<Item = &SimpleHost> {
+        self.graph.node_weights()
+    }
+
+    pub fn link_indices(&self) -> impl Iterator<Item = LinkIndex> + '_ {
+        self.graph.edge_indices()
+    }
+
+    pub fn find_host(&self, addr: IpAddr) -> Option<HostIndex> {
+        self.addresses.get(&addr).copied()
+    }
+
+    pub fn degree(&self, host_index: HostIndex) -> usize {
+        self.graph.neighbors(host_index).count()
+    }
+
+    /// Every distinct traceroute prefix that reaches the host, each ending at it.
+    pub fn traces_to(&self, host_index: HostIndex) -> Vec<Vec<SimpleHop>> {
+        let mut paths: Vec<Vec<SimpleHop>> = Vec::new();
+        let addr = self.graph[host_index].main_addr;
+        for host in self.hosts() {
+            if let Some(position) = host.trace.iter().position(|hop| hop.addr == addr) {
+                let path = &host.trace[..=position];
+                let is_known = paths.iter().any(|known| {
+                    known
+                        .iter()
+                        .map(|hop| hop.addr)
+                        .eq(path.iter().map(|hop| hop.addr))
+                });
+                if !is_known {
+                    paths.push(path.to_vec());
+                }
+            }
+        }
+        paths
+    }
+
+    /// The hosts and links a traceroute passes through, starting from the root.
+    pub fn trace_route(&self, hops: &[SimpleHop]) -> (Vec<HostIndex>, Vec<LinkIndex>) {
+        let mut previous = Self::root();
+        let mut hosts = vec![previous];
+        let mut links = Vec::new();
+        for hop in hops {
+            if let Some(index) = self.find_host(hop.addr) {
+                if let Some(link) = self.graph.find_edge(previous, index) {
+                    links.push(link);
+                }
+                hosts.push(index);
+                previous = index;
+            }
+        }
+        (hosts, links)
+    }
+
+    /// Insert a host, merging it into the existing one if its address is already known.
+    pub fn add_host(&mut self, host: SimpleHost) -> HostIndex {
+        match self.addresses.get(&host.main_addr) {
+            Some(&index) => {
+                self.graph[index].merge(host);
+                index
+            }
+            None => {
+                let addr = host.main_addr;
+                let index = self.graph.add_node(host);
+                self.addresses.insert(addr, index);
+                index
+            }
+        }
+    }
+
+    /// Link a traceroute to `target` hop by hop, starting from the scanner.
+    pub fn add_trace(&mut self, target: IpAddr, hops: &[SimpleHop]) {
+        let mut origin_index = Topology::root();
+        for hop in hops {
+            let index = match self.addresses.get(&hop.addr) {
+                Some(&index) => {
+                    if let (None, Some(rtt)) = (self.graph[index].rtt, hop.rtt) {
+                        SimpleHost::set_rtt(&mut self.graph[index], rtt);
+                    }
+                    index
+                }
+                None => self.add_host(SimpleHost::from_hop(hop)),
+            };
+            if index != origin_index {
+                self.add_trace_link(origin_index, index, target);
+            }
+            origin_index = index;
+        }
+    }
+
+    fn add_trace_link(&mut self, origin_index: HostIndex, index: HostIndex, target: IpAddr) {
+        // Traces sharing a path reuse the same link rather than stacking duplicates, and a host traced again
+        // is only counted once.
+        match self.graph.find_edge(origin_index, index) {
+            Some(edge) => {
+                let traces = &mut self.graph[edge].traces;
+                if !traces.contains(&target) {
+                    traces.push(target);
+                }
+            }
+            None => {
+                let rtt_delta = self.graph[origin_index]
+                    .rtt
+                    .zip(self.graph[index].rtt)
+                    .map(|(origin_rtt, rtt)| rtt - origin_rtt);
+                self.graph.add_edge(
+                    origin_index,
+                    index,
+                    SimpleLink {
+                        traces: vec![target],
+                        rtt_delta,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Add a link between two known hosts, combining it with any existing link between them.
+    pub fn add_link(
+        &mut self,
+        source: IpAddr,
+        target: IpAddr,
+        link: SimpleLink,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let source_index = self
+            .find_host(source)
+            .ok_or(format!("Link source {source} is not a known host."))?;
+        let target_index = self
+            .find_host(target)
+            .ok_or(format!("Link target {target} is not a known host."))?;
+
+        match self.graph.find_edge(source_index, target_index) {
+            Some(edge) => {
+                let known = &mut self.graph[edge];
+                for trace in link.traces {
+                    if !known.traces.contains(&trace) {
+                        known.traces.push(trace);
+                    }
+                }
+                known.rtt_delta = known.rtt_delta.or(link.rtt_delta);
+            }
+            None => {
+                self.graph.add_edge(source_index, target_index, link);
+            }
+        }
+        Ok(())
+    }
+
+    /// Insert a scanned host along with the links of its traceroute. A host traced again along a different
+    /// route leaves the links of its old route, and links no trace uses any more are removed.
+    pub fn add_scanned_host(&mut self, host: SimpleHost) -> HostIndex {
+        let main_addr = host.main_addr;
+        let trace = host.trace.clone();
+        if let Some(known) = self.find_host(main_addr) {
+            let old_trace = self.graph[known].trace.clone();
+            let rerouted = !trace.is_empty()
+                && !old_trace
+                    .iter()
+                    .map(|hop| hop.addr)
+                    .eq(trace.iter().map(|hop| hop.addr));
+            if rerouted {
+                self.remove_trace(main_addr, &old_trace);
+            }
+        }
+        let index = self.add_host(host);
+        self.add_trace(main_addr, &trace);
+        if !trace.is_empty() {
+            // A host attached to the scanner for want of a route has one now.
+            if let Some(link) = self.graph.find_edge(Self::root(), index) {
+                if self.graph[link].traces.is_empty() {
+                    self.graph.remove_edge(link);
+                }
+            }
+        }
+        index
+    }
+
+    /// Insert a host from a fresh scan of hosts already in the topology. When it was port scanned, its ports
+    /// replace the known ones, since ports it no longer lists have closed or been filtered since.
+    pub fn add_rescanned_host(&mut self, host: SimpleHost) -> HostIndex {
+        if host.ports_scanned {
+            if let Some(known) = self.find_host(host.main_addr) {
+                self.graph[known].ports.clear();
+            }
+        }
+        self.add_scanned_host(host)
+    }
+
+    /// Take a traceroute to `target` off the links along it, removing links left without any trace.
+    fn remove_trace(&mut self, target: IpAddr, hops: &[SimpleHop]) {
+        let (_, links) = self.trace_route(hops);
+        for link in links {
+            let traces = &mut self.graph[link].traces;
+            traces.retain(|trace| *trace != target);
+            if traces.is_empty() {
+                self.graph.remove_edge(link);
+            }
+        }
+    }
+
+    /// Fold another topology into this one, merging hosts by address and combining their links.
+    pub fn merge(&mut self, other: &Topology) -> Result<(), Box<dyn std::error::Error>> {
+        for host in other.hosts() {
+            self.add_host(host.clone());
+        }
+        for link_index in other.link_indices() {
+            if let Some((source, target)) = other.graph.edge_endpoints(link_index) {
+                self.add_link(
+                    other.graph[source].main_addr,
+                    other.graph[target].main_addr,
+                    other.graph[link_index].clone(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Link every scanned host that has no links to the scanner, so hosts found without a traceroute,
+    /// such as those from port sweepers, are not left floating apart from the rest.
+    pub fn attach_orphans(&mut self) {
+        let orphans: Vec<HostIndex> = self
+            .host_indices()
+            .filter(|n| *n != Self::root() && self.graph[*n].scanned && self.degree(*n) == 0)
+            .collect();
+        for host_index in orphans {
+            self.graph
+                .add_edge(Self::root(), host_index, SimpleLink::default());
+        }
+    }
+
+    /// Gather hosts under a representative, flattening any groups among them, and return every member.
+    pub fn group(
+        &mut self,
+        representative: HostIndex,
+        hosts: impl IntoIterator<Item = HostIndex>,
+    ) -> Vec<HostIndex> {
+        let mut members = Vec::new();
+        for host_index in hosts {
+            if host_index == representative {
+                continue;
+            }
+            if let Some(nested) = self.groups.remove(&host_index) {
+                members.extend(nested);
+            }
+            members.push(host_index);
+        }
+        if members.is_empty() {
+            return members;
+        }
+        if let Some(existing) = self.groups.remove(&representative) {
+            members.extend(existing);
+        }
+        for member in &members {
+            self.grouped_into.insert(*member, representative);
+        }
+        self.groups.insert(representative, members.clone());
+        members
+    }
+
+    /// Dissolve the group the host stands in for, returning its members.
+    pub fn ungroup(&mut self, representative: HostIndex) -> Vec<HostIndex> {
+        let members = self.groups.remove(&representative).unwrap_or_default();
+        for member in &members {
+            self.grouped_into.remove(member);
+        }
+        members
+    }
+
+    pub fn is_group(&self, host_index: HostIndex) -> bool {
+        self.groups.contains_key(&host_index)
+    }
+
+    pub fn group_members(&self, representative: HostIndex) -> Option<&[HostIndex]> {
+        self.groups.get(&representative).map(Vec::as_slice)
+    }
+
+    /// The host standing in for the group this host belongs to, if any.
+    pub fn grouped_into(&self, host_index: HostIndex) -> Option<HostIndex> {
+        self.grouped_into.get(&host_index).copied()
+    }
+}
+
+/// Build the topology from a parsed nmap scan.
+pub fn build_topology(scan: rust_nmap::nmap_run) -> Result<Topology, Box<dyn std::error::Error>> {
+    let mut topology = Topology::new()?;
+
+    let host_list = scan.host.as_ref().ok_or("Could not find scan host list.")?;
+
+    for host in host_list {
+        if let Ok(main) = SimpleHost::from_fullhost(host) {
+            topology.add_scanned_host(main);
+        }
+    }
+
+    Ok(topology)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::SimplePort;
+
+    fn traced(addr: &str, route: &[&str]) -> SimpleHost {
+        let mut host = SimpleHost::new(addr.parse().unwrap());
+        host.scanned = true;
+        host.trace = route
+            .iter()
+            .enumerate()
+            .map(|(hop, addr)| SimpleHop {
+                ttl: Some(hop as u32 + 1),
+                addr: addr.parse().unwrap(),
+                hostname: None,
+                rtt: None,
+                loss: None,
+                jitter: None,
+            })
+            .collect();
+        host
+    }
+
+    fn link<'a>(topology: &'a Topology, a: &str, b: &str) -> Option<&'a SimpleLink> {
+        let a = topology.find_host(a.parse().unwrap())?;
+        let b = topology.find_host(b.parse().unwrap())?;
+        Some(&topology.graph[topology.graph.find_edge(a, b)?])
+    }
+
+    fn trace_count(topology: &Topology, a: &str, b: &str) -> usize {
+        link(topology, a, b).unwrap().traces.len()
+    }
+
+    #[test]
+    fn rescanning_a_host_counts_its_trace_once() {
+        let mut topology = Topology::new().unwrap();
+        for _ in 0..3 {
+            topology.add_scanned_host(traced("10.0.1.5", &["10.0.0.1", "10.0.1.5"]));
+        }
+        assert_eq!(trace_count(&topology, "127.0.0.1", "10.0.0.1"), 1);
+        assert_eq!(trace_count(&topology, "10.0.0.1", "10.0.1.5"), 1);
+        assert_eq!(topology.link_indices().count(), 2);
+    }
+
+    #[test]
+    fn merging_a_topology_into_itself_adds_no_traces() {
+        let mut topology = Topology::new().unwrap();
+        topology.add_scanned_host(traced("10.0.1.5", &["10.0.0.1", "10.0.1.5"]));
+        topology.add_scanned_host(traced("10.0.1.6", &["10.0.0.1", "10.0.1.6"]));
+        let copy = topology.clone();
+        topology.merge(&copy).unwrap();
+        assert_eq!(trace_count(&topology, "127.0.0.1", "10.0.0.1"), 2);
+        assert_eq!(trace_count(&topology, "10.0.0.1", "10.0.1.6"), 1);
+    }
+
+    #[test]
+    fn a_new_route_replaces_the_old_one() {
+        let mut topology = Topology::new().unwrap();
+        topology.add_scanned_host(traced("10.0.1.5", &["10.0.0.1", "10.0.1.5"]));
+        topology.add_scanned_host(traced("10.0.1.6", &["10.0.0.1", "10.0.1.6"]));
+        topology.add_scanned_host(traced("10.0.1.5", &["10.0.0.2", "10.0.1.5"]));
+
+        // The shared first hop keeps the other host's trace; the old last link goes.
+        assert_eq!(trace_count(&topology, "127.0.0.1", "10.0.0.1"), 1);
+        assert!(link(&topology, "10.0.0.1", "10.0.1.5").is_none());
+        assert_eq!(trace_count(&topology, "127.0.0.1", "10.0.0.2"), 1);
+        assert_eq!(trace_count(&topology, "10.0.0.2", "10.0.1.5"), 1);
+    }
+
+    #[test]
+    fn rescans_replace_the_ports_they_scanned() {
+        let port = |portid, state: &str| SimplePort {
+            protocol: "tcp".to_string(),
+            portid,
+            state: state.to_string(),
+            service: None,
+            product: None,
+            version: None,
+        };
+        let mut topology = Topology::new().unwrap();
+        let mut host = traced("10.0.1.5", &[]);
+        host.ports = vec![port(22, "open"), port(23, "open")];
+        host.ports_scanned = true;
+        topology.add_scanned_host(host.clone());
+
+        host.ports = vec![port(22, "open")];
+        let index = topology.add_rescanned_host(host.clone());
+        let ports: Vec<u16> = topology.graph[index]
+            .ports
+            .iter()
+            .map(|port| port.portid)
+            .collect();
+        assert_eq!(ports, [22]);
+
+        // A rescan that found nothing to say about ports, such as a ping sweep, keeps them.
+        host.ports = Vec::new();
+        host.ports_scanned = false;
+        topology.add_rescanned_host(host);
+        assert_eq!(topology.graph[index].ports.len(), 1);
+    }
+
+    #[test]
+    fn a_traced_host_leaves_the_scanner() {
+        let mut topology = Topology::new().unwrap();
+        topology.add_scanned_host(traced("10.0.1.5", &[]));
+        topology.attach_orphans();
+        assert!(link(&topology, "127.0.0.1", "10.0.1.5").is_some());
+
+        topology.add_scanned_host(traced("10.0.1.5", &["10.0.0.1", "10.0.1.5"]));
+        assert!(link(&topology, "127.0.0.1", "10.0.1.5").is_none());
+        assert_eq!(trace_count(&topology, "10.0.0.1", "10.0.1.5"), 1);
+    }
+}
@@ -0,0 +1,329 @@
+//! Loading routes measured by standalone tools: traceroute and paris-traceroute text output, and `mtr --json`.
+//!
+//! Each route becomes a host for its destination whose trace holds the hops, just as nmap's `--traceroute`
+//! records them, so the routes link up with nmap results in the same topology.
+
+use crate::model::{SimpleHop, SimpleHost, SimpleHostname};
+use crate::topology::Topology;
+use domain::base::Dname;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+#[derive(Deserialize)]
+struct MtrDocument {
+    report: MtrReport,
+}
+
+#[derive(Deserialize)]
+struct MtrReport {
+    mtr: MtrRun,
+    #[serde(default)]
+    hubs: Vec<MtrHub>,
+}
+
+#[derive(Deserialize)]
+struct MtrRun {
+    dst: String,
+}
+
+#[derive(Deserialize)]
+struct MtrHub {
+    /// The hop's TTL, written as a string by older mtr versions.
+    count: serde_json::Value,
+    host: String,
+    #[serde(rename = "Loss%")]
+    loss: Option<f32>,
+    #[serde(rename = "Avg")]
+    avg: Option<f32>,
+    #[serde(rename = "StDev")]
+    stdev: Option<f32>,
+    /// Mean jitter, only reported when requested with `-o J`.
+    #[serde(rename = "Javg")]
+    javg: Option<f32>,
+}
+
+/// The host at the end of a route, with the route as its trace.
+fn route_host(addr: IpAddr, hostname: Option<&str>, trace: Vec<SimpleHop>) -> SimpleHost {
+    let mut host = SimpleHost::new(addr);
+    host.scanned = true;
+    host.main_hostname = hostname.and_then(|name| Dname::from_chars(name.chars()).ok());
+    host.hostnames = hostname
+        .iter()
+        .map(|name| SimpleHostname {
+            name: name.to_string(),
+            kind: Some("user".to_string()),
+        })
+        .collect();
+    host.trace = trace;
+    host
+}
+
+/// Split `name (address)` into its parts, or read a bare address.
+fn parse_name_and_address(text: &str) -> Option<(IpAddr, Option<&str>)> {
+    match text
+        .trim()
+        .strip_suffix(')')
+        .and_then(|text| text.split_once(" ("))
+    {
+        Some((name, addr)) => Some((addr.parse().ok()?, Some(name).filter(|name| *name != addr))),
+        None => Some((text.trim().parse().ok()?, None)),
+    }
+}
+
+/// Read the destination from a header such as `traceroute to example.com (93.184.216.34), 30 hops max`
+/// or paris-traceroute's `traceroute [(10.0.0.2:33456) -> (93.184.216.34:33457)], protocol udp`.
+fn parse_header(line: &str) -> Option<(IpAddr, Option<&str>)> {
+    if let Some(target) = line.strip_prefix("traceroute to ") {
+        return parse_name_and_address(target.split(',').next()?);
+    }
+    let destination = line.split_once("-> (")?.1.split(')').next()?;
+    let addr = match destination.parse() {
+        Ok(addr) => addr,
+        // The destination carries a port unless the probes were ICMP.
+        Err(_) => destination.rsplit_once(':')?.0.parse().ok()?,
+    };
+    Some((addr, None))
+}
+
+/// Parse a hop line such as ` 4  a.example (10.0.0.1)  10.1 ms  10.3 ms b.example (10.0.0.5)  11.0 ms`.
+/// Only the first router to answer at each TTL is kept, with the mean of its round-trip times.
+fn parse_hop(line: &str) -> Option<SimpleHop> {
+    let mut words = line.split_whitespace();
+    let ttl = words.next()?.parse().ok()?;
+
+    let mut responder: Option<(Option<&str>, Option<IpAddr>)> = None;
+    let mut rtts = Vec::new();
+    for word in words {
+        // Unanswered probes and annotations such as !H or paris-traceroute's !T2.
+        if word == "*" || word == "ms" || word.starts_with('!') {
+            continue;
+        }
+        if let Ok(rtt) = word.parse::<f32>() {
+            rtts.push(rtt);
+        } else if let Some(addr) = word
+            .strip_prefix('(')
+            .and_then(|word| word.strip_suffix(')'))
+        {
+            if let Some((_, known)) = responder.as_mut() {
+                *known = known.or(addr.parse().ok());
+            }
+        } else if responder.is_some() {
+            break;
+        } else {
+            responder = Some(match word.parse() {
+                Ok(addr) => (None, Some(addr)),
+                Err(_) => (Some(word), None),
+            });
+        }
+    }
+
+    let (hostname, addr) = responder?;
+    Some(SimpleHop {
+        ttl: Some(ttl),
+        addr: addr?,
+        hostname: hostname.map(str::to_string),
+        rtt: (!rtts.is_empty()).then(|| rtts.iter().sum::<f32>() / rtts.len() as f32),
+        loss: None,
+        jitter: None,
+    })
+}
+
+/// A route being read: its destination, if a header named it, and its hops so far.
+type Route<'a> = (Option<(IpAddr, Option<&'a str>)>, Vec<SimpleHop>);
+
+fn add_route(topology: &mut Topology, (destination, hops): Route) {
+    // traceroute writes its header to stderr, so captures of stdout alone have none; the last hop to answer
+    // then stands in for the destination.
+    let (addr, hostname) = match destination {
+        Some((addr, hostname)) => (addr, hostname.map(str::to_string)),
+        None => match hops.last() {
+            Some(hop) => (hop.addr, hop.hostname.clone()),
+            None => return,
+        },
+    };
+    topology.add_scanned_host(route_host(addr, hostname.as_deref(), hops));
+}
+
+/// Whether a line reads as a numbered hop, for recognising output captured without its header.
+pub(crate) fn is_hop_line(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    words.next().is_some_and(|ttl| ttl.parse::<u32>().is_ok()) && words.next().is_some()
+}
+
+/// Build the topology from traceroute or paris-traceroute output, which may hold several routes one after another.
+pub fn import_traceroute(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut topology = Topology::new()?;
+    let mut route: Option<Route> = None;
+
+    for line in text.lines() {
+        if line.starts_with("traceroute") {
+            if let Some(route) = route.take() {
+                add_route(&mut topology, route);
+            }
+            let (addr, hostname) =
+                parse_header(line).ok_or(format!("Could not find the destination in: {line}"))?;
+            route = Some((Some((addr, hostname)), Vec::new()));
+        } else if let Some(hop) = parse_hop(line) {
+            let restarted = match &route {
+                None => true,
+                // Without headers, a TTL that does not go up starts the next route.
+                Some((None, hops)) => hops.last().is_some_and(|last| hop.ttl <= last.ttl),
+                Some(_) => false,
+            };
+            if restarted {
+                if let Some(route) = route.take() {
+                    add_route(&mut topology, route);
+                }
+            }
+            route.get_or_insert((None, Vec::new())).1.push(hop);
+        }
+    }
+    if let Some(route) = route {
+        add_route(&mut topology, route);
+    }
+
+    Ok(topology)
+}
+
+/// Build the topology from `mtr --json`, keeping each hop's loss and jitter.
+/// Hops are only placed when mtr reports their address, so run it with `-n` or `-b`.
+pub fn import_mtr(bytes: &[u8]) -> Result<Topology, Box<dyn std::error::Error>> {
+    let document: MtrDocument = serde_json::from_slice(bytes)?;
+    let report = document.report;
+
+    let hops: Vec<SimpleHop> = report
+        .hubs
+        .iter()
+        .filter_map(|hub| {
+            let (addr, hostname) = parse_name_and_address(&hub.host)?;
+            let ttl = match &hub.count {
+                serde_json::Value::Number(count) => count.as_u64().map(|count| count as u32),
+                serde_json::Value::String(count) => count.parse().ok(),
+                _ => None,
+            };
+            Some(SimpleHop {
+                ttl,
+                addr,
+                hostname: hostname.map(str::to_string),
+                rtt: hub.avg,
+                loss: hub.loss,
+                jitter: hub.javg.or(hub.stdev),
+            })
+        })
+        .collect();
+
+    // The destination may be a name; the last hop to answer then stands in for its address.
+    let (addr, hostname) = match report.mtr.dst.parse() {
+        Ok(addr) => (addr, None),
+        Err(_) => (
+            hops.last()
+                .ok_or(format!("mtr never reached {}.", report.mtr.dst))?
+                .addr,
+            Some(report.mtr.dst.as_str()),
+        ),
+    };
+
+    let mut topology = Topology::new()?;
+    topology.add_scanned_host(route_host(addr, hostname, hops));
+    Ok(topology)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::InputFormat;
+    use std::path::Path;
+
+    const ROUTE: &str = " 1  gw.example.net (10.0.0.1)  0.412 ms  0.380 ms  0.371 ms\n\
+        \x20 2  * * *\n\
+        \x20 3  a.example.net (10.1.0.1)  10.100 ms b.example.net (10.1.0.5)  11.000 ms  10.300 ms\n\
+        \x20 4  example.com (93.184.216.34)  20.000 ms  20.000 ms  20.000 ms\n";
+
+    fn host<'a>(topology: &'a Topology, addr: &str) -> &'a SimpleHost {
+        &topology.graph[topology.find_host(addr.parse().unwrap()).unwrap()]
+    }
+
+    fn trace_addresses(host: &SimpleHost) -> Vec<String> {
+        host.trace.iter().map(|hop| hop.addr.to_string()).collect()
+    }
+
+    #[test]
+    fn unanswered_and_multi_responder_hops() {
+        let output = format!(
+            "traceroute to example.com (93.184.216.34), 30 hops max, 60 byte packets\n{ROUTE}"
+        );
+        let topology = import_traceroute(output.as_bytes()).unwrap();
+
+        let destination = host(&topology, "93.184.216.34");
+        assert!(destination.scanned);
+        assert_eq!(destination.hostnames[0].name, "example.com");
+        // The unanswered TTL is left out, and only the first router to answer at TTL 3 is kept.
+        assert_eq!(
+            trace_addresses(destination),
+            ["10.0.0.1", "10.1.0.1", "93.184.216.34"]
+        );
+        let first = &destination.trace[0];
+        assert_eq!(first.ttl, Some(1));
+        assert_eq!(first.hostname.as_deref(), Some("gw.example.net"));
+        assert!((first.rtt.unwrap() - 0.388).abs() < 0.001);
+        assert_eq!(destination.trace[1].ttl, Some(3));
+        assert!((destination.trace[1].rtt.unwrap() - 10.1).abs() < 0.001);
+        assert!(topology.find_host("10.1.0.5".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn paris_traceroute_header() {
+        assert_eq!(
+            parse_header(
+                "traceroute [(10.0.0.2:33456) -> (93.184.216.34:33457)], protocol udp, algo hopbyhop, duration 3 s"
+            ),
+            Some(("93.184.216.34".parse().unwrap(), None))
+        );
+    }
+
+    #[test]
+    fn headerless_routes_end_at_their_last_hop() {
+        assert_eq!(
+            InputFormat::detect(None, ROUTE.as_bytes()),
+            InputFormat::Traceroute
+        );
+        // Two captures one after the other, told apart by the TTLs starting over.
+        let output =
+            format!("{ROUTE} 1  gw.example.net (10.0.0.1)  0.400 ms\n 2  10.2.0.9  3.000 ms\n");
+        let topology = import_traceroute(output.as_bytes()).unwrap();
+
+        let destination = host(&topology, "93.184.216.34");
+        assert!(destination.scanned);
+        assert_eq!(destination.hostnames[0].name, "example.com");
+        assert_eq!(destination.trace.len(), 3);
+        assert_eq!(
+            trace_addresses(host(&topology, "10.2.0.9")),
+            ["10.0.0.1", "10.2.0.9"]
+        );
+    }
+
+    #[test]
+    fn mtr_json_is_recognised_by_content() {
+        let output = r#"{"report": {
+            "mtr": {"src": "scanner", "dst": "example.com", "tos": 0, "tests": 10, "psize": "64", "bitpattern": "0x00"},
+            "hubs": [
+                {"count": 1, "host": "10.0.0.1", "Loss%": 0.0, "Snt": 10, "Last": 0.4, "Avg": 0.5, "Best": 0.3, "Wrst": 0.9, "StDev": 0.1},
+                {"count": "2", "host": "???", "Loss%": 100.0, "Snt": 10, "Last": 0.0, "Avg": 0.0, "Best": 0.0, "Wrst": 0.0, "StDev": 0.0},
+                {"count": 3, "host": "93.184.216.34", "Loss%": 10.0, "Snt": 10, "Last": 20.0, "Avg": 20.1, "Best": 19.8, "Wrst": 21.0, "StDev": 0.4}
+            ]
+        }}"#;
+        assert_eq!(
+            InputFormat::detect(Some(Path::new("route.json")), output.as_bytes()),
+            InputFormat::Mtr
+        );
+        let topology = import_mtr(output.as_bytes()).unwrap();
+
+        let destination = host(&topology, "93.184.216.34");
+        assert_eq!(destination.hostnames[0].name, "example.com");
+        assert_eq!(trace_addresses(destination), ["10.0.0.1", "93.184.216.34"]);
+        assert_eq!(destination.trace[1].ttl, Some(3));
+        assert_eq!(destination.trace[1].loss, Some(10.0));
+        assert_eq!(destination.trace[1].jitter, Some(0.4));
+    }
+}
@@ -0,0 +1,2134 @@
+//! The interactive kiss3d viewer.
+
+use kiss3d::camera::*;
+use kiss3d::conrod;
+use kiss3d::conrod::image;
+use kiss3d::event::Key;
+use kiss3d::event::Modifiers;
+use kiss3d::event::MouseButton;
+use kiss3d::event::{Action, WindowEvent};
+use kiss3d::light::Light;
+use kiss3d::nalgebra::{Point2, Point3, Translation3, Vector2, Vector3};
+use kiss3d::resource::TextureManager;
+use kiss3d::scene::SceneNode;
+use kiss3d::window::Window;
+use neuromaster::topology::{HostIndex, LinkIndex, Topology};
+use std::collections::{HashMap, HashSet};
+
+use kiss3d::conrod::position::Positionable;
+use kiss3d::conrod::widget_ids;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc::Receiver;
+
+use neuromaster::history::{self, History};
+use neuromaster::import::{InputFormat, LoadedGraph};
+use neuromaster::labels::{LabelMode, LabelPlacer};
+use neuromaster::layout::{self, Layout};
+use neuromaster::model::{OsGuess, SimpleHop, SimpleHost};
+use neuromaster::ndiff::{Change, HostDiff, ScanDiff};
+use neuromaster::render::{self, OrbitCamera, RenderOptions};
+use neuromaster::scan::{self, ScanProfile};
+use neuromaster::stats::{self, ScanStats};
+use neuromaster::stream::{self, LoadMessage, Progress};
+use neuromaster::subset;
+use neuromaster::{export, flythrough, import, inventory, labels, report};
+
+const SELECTED_COLOR: (f32, f32, f32) = (0.0, 0.0, 1.0);
+const DEFAULT_COLOR: (f32, f32, f32) = (1.0, 0.0, 0.0);
+const PATH_COLOR: (f32, f32, f32) = (1.0, 0.5, 0.0);
+const FADED_COLOR: (f32, f32, f32) = (0.3, 0.0, 0.0);
+// Hosts an ndiff overlay marks as added, removed or changed.
+const ADDED_COLOR: (f32, f32, f32) = (0.0, 0.8, 0.0);
+const REMOVED_COLOR: (f32, f32, f32) = (0.4, 0.4, 0.4);
+const CHANGED_COLOR: (f32, f32, f32) = (1.0, 1.0, 0.0);
+
+const TEXT_COLOR: Point3<f32> = Point3::new(1.0, 1.0, 1.0);
+const LABEL_SIZE: f32 = 24.0;
+// Rough advance of one label character at LABEL_SIZE, used to size labels for collision checks.
+const LABEL_CHAR_WIDTH: f32 = 13.0;
+// Ordinary labels fade out between these camera distances; prioritized labels never do.
+const LABEL_FADE_DISTANCE: f32 = 80.0;
+const LABEL_CULL_DISTANCE: f32 = 160.0;
+// Nodes with at least this many neighbours get their labels placed before leaf nodes.
+const HIGH_DEGREE: usize = 3;
+const TOOLTIP_COLOR: Point3<f32> = Point3::new(1.0, 1.0, 0.5);
+const LINE_COLOR: Point3<f32> = Point3::new(0.0, 1.0, 0.0);
+const SELECTED_LINE_COLOR: Point3<f32> = Point3::new(0.0, 0.5, 1.0);
+const PATH_LINE_COLOR: Point3<f32> = Point3::new(0.0, 1.0, 1.0);
+const FADED_LINE_COLOR: Point3<f32> = Point3::new(0.0, 0.25, 0.0);
+// Far end of the rtt gradient; near end is LINE_COLOR.
+const SLOW_LINE_COLOR: Point3<f32> = Point3::new(1.0, 1.0, 0.0);
+
+const NODE_RADIUS: f32 = 1.0;
+// How close a ray must pass to an edge for Ctrl+LMB to pick it.
+const EDGE_PICK_RADIUS: f32 = 0.5;
+// Spacing between the strands of a thickened edge.
+const EDGE_STRAND_SPACING: f32 = 0.08;
+const MAX_EDGE_STRANDS: usize = 5;
+const ARROW_LENGTH: f32 = 0.8;
+const ARROW_WIDTH: f32 = 0.3;
+// Scale applied to the node standing in for a collapsed group.
+const GROUP_SCALE: f32 = 2.0;
+// Cursor travel, in pixels, beyond which a Ctrl/Shift+LMB press becomes a box selection.
+const DRAG_THRESHOLD: f32 = 4.0;
+// Tooltip placement relative to the cursor, and spacing between its lines, in pixels.
+const TOOLTIP_OFFSET: f32 = 16.0;
+const TOOLTIP_LINE_HEIGHT: f32 = 12.0;
+// Open ports listed in a tooltip before the rest are summarized as a count.
+const TOOLTIP_PORTS: usize = 4;
+
+// Distance of the loading indicator from the bottom-left corner of the window.
+const LOADING_MARGIN: f32 = 20.0;
+// Most messages taken from a background load each frame, so a fast load cannot stall drawing.
+const LOAD_BATCH: usize = 1000;
+
+const SELECTION_EXPORT_PATH: &str = "selection.json";
+const SELECTION_XML_PATH: &str = "selection.xml";
+// Where "Save nmap command" writes the rescan's targets, and where the saved command writes its XML.
+const RESCAN_TARGETS_PATH: &str = "rescan-targets.txt";
+const RESCAN_OUTPUT_PATH: &str = "rescan.xml";
+const DEFAULT_GRAPH_EXPORT_PATH: &str = "topology.graphml";
+const DEFAULT_INVENTORY_PATH: &str = "inventory.csv";
+const DEFAULT_REPORT_PATH: &str = "report.html";
+// Tried in order until one accepts the copied addresses on stdin.
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 3] = [
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("pbcopy", &[]),
+];
+
+/// How edges are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Every edge in LINE_COLOR.
+    Plain,
+    /// Colored along a gradient by the rtt increase across the edge.
+    RttDelta,
+    /// Thickened by the number of traces traversing the edge.
+    TraceCount,
+}
+
+impl EdgeMode {
+    pub fn next(self) -> Self {
+        match self {
+            EdgeMode::Plain => EdgeMode::RttDelta,
+            EdgeMode::RttDelta => EdgeMode::TraceCount,
+            EdgeMode::TraceCount => EdgeMode::Plain,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EdgeMode::Plain => "plain",
+            EdgeMode::RttDelta => "rtt delta",
+            EdgeMode::TraceCount => "trace count",
+        }
+    }
+}
+
+trait WindowExt {
+    fn alloc_conrod_texture(&mut self, bytes: &[u8], name: &str) -> image::Id;
+}
+
+impl WindowExt for Window {
+    fn alloc_conrod_texture(&mut self, bytes: &[u8], name: &str) -> image::Id {
+        TextureManager::get_global_manager(|tm| tm.add_image_from_memory(bytes, name));
+        self.conrod_texture_id(name).unwrap()
+    }
+}
+
+trait SceneNodeExt {
+    fn paint_default(&mut self);
+    fn paint_selected(&mut self);
+    fn paint_path(&mut self);
+    fn paint_faded(&mut self);
+    fn paint_change(&mut self, change: Change);
+}
+
+impl SceneNodeExt for SceneNode {
+    fn paint_default(&mut self) {
+        self.set_color(DEFAULT_COLOR.0, DEFAULT_COLOR.1, DEFAULT_COLOR.2);
+    }
+
+    fn paint_selected(&mut self) {
+        self.set_color(SELECTED_COLOR.0, SELECTED_COLOR.1, SELECTED_COLOR.2);
+    }
+
+    fn paint_path(&mut self) {
+        self.set_color(PATH_COLOR.0, PATH_COLOR.1, PATH_COLOR.2);
+    }
+
+    fn paint_faded(&mut self) {
+        self.set_color(FADED_COLOR.0, FADED_COLOR.1, FADED_COLOR.2);
+    }
+
+    fn paint_change(&mut self, change: Change) {
+        let (r, g, b) = match change {
+            Change::Added => ADDED_COLOR,
+            Change::Removed => REMOVED_COLOR,
+            Change::Changed => CHANGED_COLOR,
+        };
+        self.set_color(r, g, b);
+    }
+}
+
+/// Open the scans, streaming a lone nmap XML scan in on a background thread.
+/// Other formats are small enough to load up front, as are several scans to be merged.
+fn load_scans(paths: &[PathBuf]) -> (LoadedGraph, Option<Receiver<LoadMessage>>) {
+    if paths.len() <= 1 {
+        let input = import::open_input(paths.first().map(PathBuf::as_path))
+            .expect("Failed to read specified file!");
+        if input.format == InputFormat::NmapXml {
+            return (
+                LoadedGraph {
+                    topology: Topology::new().unwrap(),
+                    hosts_down: 0,
+                },
+                Some(stream::spawn_loader(input.reader, input.total_bytes)),
+            );
+        }
+    }
+    let loaded = import::load_inputs(paths, |_| {}).expect("Failed to read specified file!");
+    (loaded, None)
+}
+
+/// Open the viewer on the input named in the arguments.
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut scan_files = Vec::new();
+    let mut label_template = None;
+    let mut graph_export_path = PathBuf::from(DEFAULT_GRAPH_EXPORT_PATH);
+    let mut keyframes_path = PathBuf::from(flythrough::DEFAULT_KEYFRAMES_PATH);
+    let mut follow = false;
+    let mut nmap_path = PathBuf::from(scan::DEFAULT_NMAP);
+    let mut diff_path = None;
+    let mut inventory_path = PathBuf::from(DEFAULT_INVENTORY_PATH);
+    let mut report_path = PathBuf::from(DEFAULT_REPORT_PATH);
+    let mut history_path = None;
+    let mut open_scan = None;
+    let mut diff_since = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--label-template" => {
+                label_template = Some(args.next().expect("--label-template requires a template!"))
+            }
+            "--export" => {
+                graph_export_path = PathBuf::from(args.next().expect("--export requires a path!"))
+            }
+            "--keyframes" => {
+                keyframes_path = PathBuf::from(args.next().expect("--keyframes requires a path!"))
+            }
+            "--follow" => follow = true,
+            "--diff" => {
+                diff_path = Some(PathBuf::from(args.next().expect("--diff requires a path!")))
+            }
+            "--inventory" => {
+                inventory_path = PathBuf::from(args.next().expect("--inventory requires a path!"))
+            }
+            "--report" => {
+                report_path = PathBuf::from(args.next().expect("--report requires a path!"))
+            }
+            "--history" => {
+                history_path = Some(PathBuf::from(
+                    args.next().expect("--history requires a path!"),
+                ))
+            }
+            "--open" => open_scan = Some(args.next().expect("--open requires a scan id or date!")),
+            "--diff-since" => {
+                diff_since = Some(
+                    args.next()
+                        .expect("--diff-since requires a scan id or date!"),
+                )
+            }
+            "--nmap" => nmap_path = PathBuf::from(args.next().expect("--nmap requires a path!")),
+            _ => scan_files.push(PathBuf::from(arg)),
+        }
+    }
+
+    // Past scans can be reopened or compared against without recording anything new.
+    let mut store =
+        (history_path.is_some() || open_scan.is_some() || diff_since.is_some()).then(|| {
+            let path = history_path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(history::DEFAULT_HISTORY_PATH));
+            History::open(&path).expect("Failed to open the scan history!")
+        });
+    fn load_past_scan(store: &Option<History>, spec: &str) -> LoadedGraph {
+        let store = store.as_ref().unwrap();
+        let scan_id = store
+            .find_scan(spec)
+            .expect("Failed to find the scan in the history!");
+        store
+            .load(scan_id)
+            .expect("Failed to load the scan from the history!")
+    }
+    let source = if scan_files.is_empty() {
+        history::SAMPLE_SOURCE.to_string()
+    } else {
+        let names: Vec<String> = scan_files
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        names.join(" ")
+    };
+
+    let (loaded, loader) = if let Some(spec) = &open_scan {
+        (load_past_scan(&store, spec), None)
+    } else if follow {
+        // The last file may not exist yet, or hold only a partial document, so it is read as nmap XML
+        // regardless. Any others are loaded up front for it to add to.
+        let (followed, others) = scan_files
+            .split_last()
+            .expect("--follow requires a scan file!");
+        let loaded = if others.is_empty() {
+            LoadedGraph {
+                topology: Topology::new().unwrap(),
+                hosts_down: 0,
+            }
+        } else {
+            import::load_inputs(others, |_| {}).expect("Failed to read specified file!")
+        };
+        (loaded, Some(stream::spawn_follower(followed.clone())))
+    } else if diff_since.is_some() {
+        // The comparison needs the whole scan up front.
+        let loaded =
+            import::load_inputs(&scan_files, |_| {}).expect("Failed to read specified file!");
+        (loaded, None)
+    } else {
+        load_scans(&scan_files)
+    };
+    let mut topology = loaded.topology;
+    let diff = match diff_since {
+        Some(spec) => Some(ScanDiff::compare(
+            &load_past_scan(&store, &spec).topology,
+            &topology,
+        )),
+        None => {
+            diff_path.map(|path| ScanDiff::load(&path).expect("Failed to read specified diff!"))
+        }
+    };
+    // Loads still streaming in are recorded when they finish.
+    let recording = history_path.is_some();
+    if let Some(history) = store
+        .as_mut()
+        .filter(|_| recording && loader.is_none() && open_scan.is_none())
+    {
+        match history.record(
+            &topology,
+            loaded.hosts_down,
+            &source,
+            history::modified_at(&scan_files),
+        ) {
+            Ok(scan_id) => println!("Recorded scan {scan_id} in the history"),
+            Err(e) => eprintln!("Failed to record the scan in the history: {e}"),
+        }
+    }
+    if let Some(diff) = &diff {
+        diff.apply(&mut topology);
+    }
+    let mut node_map = HashMap::<HostIndex, SceneNode>::new();
+
+    let mut window = Window::new("Neuromaster");
+
+    let tux_texture = window.alloc_conrod_texture(include_bytes!("../assets/tux.png"), "tux");
+    let puffy_texture = window.alloc_conrod_texture(include_bytes!("../assets/puffy.png"), "puffy");
+    let daemon_texture =
+        window.alloc_conrod_texture(include_bytes!("../assets/daemon.png"), "daemon");
+
+    let mut camera = kiss3d::camera::ArcBall::new(Point3::new(0.0f32, 0.0, -1.0), Point3::origin());
+    window.set_light(Light::StickToCamera);
+
+    for node_index in topology.host_indices() {
+        let scene_node = wireframe_sphere(&mut window);
+        node_map.insert(node_index, scene_node);
+    }
+
+    // saves resources AND looks very "Neuromancer"
+    fn wireframe_sphere(window: &mut Window) -> SceneNode {
+        let mut scene_node = window.add_sphere(NODE_RADIUS);
+        scene_node.paint_default();
+        scene_node.set_points_size(10.0);
+        scene_node.set_lines_width(1.0);
+        scene_node.set_surface_rendering_activation(false);
+        scene_node
+    }
+
+    let ids = Ids::new(window.conrod_ui_mut().widget_id_generator());
+    window.conrod_ui_mut().theme = theme();
+
+    let mut application_state = ApplicationState::new(
+        Layout::new(&topology),
+        topology,
+        node_map,
+        tux_texture,
+        puffy_texture,
+        daemon_texture,
+    );
+    application_state.loader = loader;
+    application_state.load_source = source;
+    application_state.history = store.filter(|_| recording);
+    application_state.hosts_down = loaded.hosts_down;
+    application_state.nmap_path = nmap_path;
+    application_state.diff = diff;
+    application_state.sample_loaded = scan_files.is_empty() && open_scan.is_none();
+    application_state.scan_files = scan_files;
+    application_state.inventory_path = inventory_path;
+    application_state.paint_nodes();
+    if let Some(template) = label_template {
+        application_state.label_mode = LabelMode::Template(template.clone());
+        application_state.label_template = Some(template);
+    }
+
+    // Keep adding to keyframes recorded in an earlier session.
+    let mut flythrough = flythrough::Flythrough::load(&keyframes_path).unwrap_or_default();
+
+    let mut last_pos = Point2::new(0.0f32, 0.0f32);
+    let mut window_size = Vector2::new(0.0, 0.0);
+    // Where the current RMB press started, to tell a right-click from a pan.
+    let mut right_click_start = None;
+    // Where the current Ctrl/Shift+LMB press started, while the button is held.
+    let mut drag_start: Option<Point2<f32>> = None;
+    let font = kiss3d::text::Font::default();
+    while window.render_with_camera(&mut camera) {
+        for mut event in window.events().iter() {
+            // Keys typed into the scan panel are not shortcuts.
+            if let WindowEvent::Key(..) = event.value {
+                if window.is_conrod_ui_capturing_keyboard() {
+                    event.inhibit();
+                    continue;
+                }
+            }
+            match event.value {
+                WindowEvent::FramebufferSize(x, y) => {
+                    window_size = Vector2::new(x as f32, y as f32);
+                }
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Press, modifiers)
+                    if modifiers.intersects(Modifiers::Control | Modifiers::Shift)
+                        && !window.is_conrod_ui_capturing_mouse() =>
+                {
+                    drag_start = Some(last_pos);
+                    event.inhibit();
+                }
+                WindowEvent::MouseButton(MouseButton::Button1, Action::Release, modifiers) => {
+                    if let Some(start) = drag_start.take() {
+                        let additive = modifiers.contains(Modifiers::Shift);
+                        if (last_pos - start).norm() < DRAG_THRESHOLD {
+                            let (ray_origin, ray_direction) =
+                                camera.unproject(&last_pos, &window_size);
+                            application_state.select_nearest_intersection(
+                                ray_origin,
+                                ray_direction,
+                                additive,
+                            );
+                        } else {
+                            application_state.select_in_rectangle(
+                                &start,
+                                &last_pos,
+                                &camera,
+                                &window_size,
+                                additive,
+                            );
+                        }
+                        event.inhibit();
+                    }
+                }
+                WindowEvent::MouseButton(MouseButton::Button2, Action::Press, _)
+                    if !window.is_conrod_ui_capturing_mouse() =>
+                {
+                    right_click_start = Some(last_pos);
+                }
+                WindowEvent::MouseButton(MouseButton::Button2, Action::Release, _) => {
+                    if let Some(start) = right_click_start.take() {
+                        if (last_pos - start).norm() < DRAG_THRESHOLD {
+                            let (ray_origin, ray_direction) =
+                                camera.unproject(&last_pos, &window_size);
+                            if let Some(node_index) = application_state
+                                .find_nearest_intersection(ray_origin, ray_direction)
+                            {
+                                application_state.confirm_rescan(node_index);
+                            }
+                        }
+                    }
+                }
+                WindowEvent::CursorPos(x, y, _modif) => {
+                    last_pos = Point2::new(x as f32, y as f32);
+                    // Keep the camera still while a selection box is being dragged.
+                    if drag_start.is_some() {
+                        event.inhibit();
+                    }
+                    let hovered = if window.is_conrod_ui_capturing_mouse() {
+                        None
+                    } else {
+                        let (ray_origin, ray_direction) = camera.unproject(&last_pos, &window_size);
+                        application_state.find_nearest_intersection(ray_origin, ray_direction)
+                    };
+                    application_state.node_hovered = hovered;
+                }
+                WindowEvent::Key(Key::E, Action::Press, _) => {
+                    application_state.edge_mode = application_state.edge_mode.next();
+                }
+                WindowEvent::Key(Key::A, Action::Press, _) => {
+                    application_state.edge_arrows = !application_state.edge_arrows;
+                }
+                WindowEvent::Key(Key::X, Action::Press, _) => {
+                    match export_graph_file(&application_state, &graph_export_path) {
+                        Ok(()) => println!("Exported topology to {}", graph_export_path.display()),
+                        Err(e) => eprintln!("Failed to export topology: {e}"),
+                    }
+                }
+                WindowEvent::Key(Key::R, Action::Press, _) => {
+                    match save_report(&application_state, &camera, &report_path) {
+                        Ok(()) => println!("Wrote report to {}", report_path.display()),
+                        Err(e) => eprintln!("Failed to write report: {e}"),
+                    }
+                }
+                WindowEvent::Key(Key::I, Action::Press, _) => {
+                    match application_state.export_inventory(false) {
+                        Ok(count) => println!(
+                            "Wrote {count} hosts to {}",
+                            application_state.inventory_path.display()
+                        ),
+                        Err(e) => eprintln!("Failed to write inventory: {e}"),
+                    }
+                }
+                WindowEvent::Key(Key::K, Action::Press, modifiers) => {
+                    if modifiers.contains(Modifiers::Shift) {
+                        flythrough.keyframes.pop();
+                    } else {
+                        let at = camera.at();
+                        flythrough.keyframes.push(flythrough::Keyframe {
+                            target: [at.x, at.y, at.z],
+                            yaw: camera.yaw(),
+                            pitch: camera.pitch(),
+                            distance: camera.dist(),
+                        });
+                    }
+                    flythrough
+                        .capture_layout(&application_state.topology, &application_state.layout);
+                    match flythrough.save(&keyframes_path) {
+                        Ok(()) => println!(
+                            "Saved {} keyframes to {}",
+                            flythrough.keyframes.len(),
+                            keyframes_path.display()
+                        ),
+                        Err(e) => eprintln!("Failed to save keyframes: {e}"),
+                    }
+                }
+                WindowEvent::Key(Key::N, Action::Press, _) => {
+                    application_state.label_nodes = !application_state.label_nodes;
+                }
+                WindowEvent::Key(Key::L, Action::Press, _) => {
+                    application_state.label_mode = application_state
+                        .label_mode
+                        .next(application_state.label_template.as_deref());
+                }
+                _ => {}
+            }
+        }
+
+        {
+            let mut ui = window.conrod_ui_mut().set_widgets();
+            application_state.gui(&mut ui, &ids);
+        }
+
+        let added = application_state.poll_loader();
+        for node_index in &added {
+            let scene_node = wireframe_sphere(&mut window);
+            application_state.node_map.insert(*node_index, scene_node);
+        }
+        if !added.is_empty() {
+            application_state.paint_nodes();
+        }
+        application_state.layout.update(layout::STEP);
+        application_state.apply_pins();
+        for (node_index, [x, y, z]) in application_state.layout.positions() {
+            let scene_node = application_state.node_map.get_mut(&node_index).unwrap();
+            scene_node.set_local_translation(Translation3::new(x, y, z));
+        }
+        application_state.draw_labels(&mut window, &camera, &window_size, &font);
+        application_state.draw_edges(&mut window, &camera.eye());
+        if let Some(start) = drag_start {
+            draw_selection_rectangle(&mut window, &start, &last_pos, &window_size);
+        }
+
+        for (line_number, line) in application_state.get_tooltip().iter().enumerate() {
+            let screen_position = &Point2::new(
+                2.0 * (last_pos.x + TOOLTIP_OFFSET),
+                2.0 * (last_pos.y + TOOLTIP_OFFSET + line_number as f32 * TOOLTIP_LINE_HEIGHT),
+            );
+            window.draw_text(line, screen_position, 20.0, &font, &TOOLTIP_COLOR);
+        }
+        if let Some(progress) = &application_state.load_progress {
+            window.draw_text(
+                &format!("Loading: {progress}"),
+                &Point2::new(
+                    LOADING_MARGIN,
+                    2.0 * window_size.y - LOADING_MARGIN - LABEL_SIZE,
+                ),
+                LABEL_SIZE,
+                &font,
+                &TOOLTIP_COLOR,
+            );
+        }
+    }
+}
+
+/// Outline the screen-space rectangle between two cursor positions.
+fn draw_selection_rectangle(
+    window: &mut Window,
+    corner_a: &Point2<f32>,
+    corner_b: &Point2<f32>,
+    window_size: &Vector2<f32>,
+) {
+    // Planar coordinates are centered on the window, with y pointing up.
+    let to_planar = |x: f32, y: f32| Point2::new(x - window_size.x / 2.0, window_size.y / 2.0 - y);
+    let corners = [
+        to_planar(corner_a.x, corner_a.y),
+        to_planar(corner_b.x, corner_a.y),
+        to_planar(corner_b.x, corner_b.y),
+        to_planar(corner_a.x, corner_b.y),
+    ];
+    for i in 0..corners.len() {
+        window.draw_planar_line(
+            &corners[i],
+            &corners[(i + 1) % corners.len()],
+            &SELECTED_LINE_COLOR,
+        );
+    }
+}
+
+/// Write the current topology and layout to a file, in the format its extension names.
+fn export_graph_file(
+    application_state: &ApplicationState,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = export::ExportFormat::from_path(path)
+        .ok_or("Unrecognized export extension; use .graphml, .gexf, .dot or .json.")?;
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    export::export_graph(
+        &application_state.topology,
+        &application_state.layout,
+        format,
+        &mut writer,
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write an HTML report on the topology, picturing it from the viewer's camera with what is on screen.
+fn save_report(
+    application_state: &ApplicationState,
+    camera: &ArcBall,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let topology = &application_state.topology;
+    let at = camera.at();
+    let options = RenderOptions {
+        width: render::DEFAULT_WIDTH,
+        height: render::DEFAULT_HEIGHT,
+        camera: OrbitCamera {
+            target: [at.x, at.y, at.z].into(),
+            yaw: camera.yaw(),
+            pitch: camera.pitch(),
+            distance: camera.dist(),
+            fov: render::DEFAULT_FOV,
+        },
+        label_mode: application_state
+            .label_nodes
+            .then(|| application_state.label_mode.clone()),
+        hidden: topology
+            .host_indices()
+            .filter(|host_index| !application_state.is_visible(*host_index))
+            .map(|host_index| topology.graph[host_index].main_addr)
+            .collect(),
+    };
+    report::save_report(
+        &report::default_title(&application_state.scan_files),
+        &ScanStats::new(topology, application_state.hosts_down),
+        topology,
+        &application_state.layout,
+        &options,
+        path,
+    )
+}
+
+/// Hand text to the first available clipboard program.
+fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn std::error::Error>> {
+    for (program, args) in CLIPBOARD_COMMANDS {
+        if let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            child
+                .stdin
+                .take()
+                .ok_or("Failed to open clipboard stdin.")?
+                .write_all(text.as_bytes())?;
+            child.wait()?;
+            return Ok(());
+        }
+    }
+    Err("No clipboard program found.".into())
+}
+
+pub fn theme() -> conrod::Theme {
+    use conrod::position::{Align, Direction, Padding, Position, Relative};
+    conrod::Theme {
+        name: "Demo Theme".to_string(),
+        padding: Padding::none(),
+        x_position: Position::Relative(Relative::Align(Align::Start), None),
+        y_position: Position::Relative(Relative::Direction(Direction::Backwards, 20.0), None),
+        background_color: conrod::color::DARK_CHARCOAL,
+        shape_color: conrod::color::LIGHT_CHARCOAL,
+        border_color: conrod::color::BLACK,
+        border_width: 0.0,
+        label_color: conrod::color::WHITE,
+        font_id: None,
+        font_size_large: 26,
+        font_size_medium: 18,
+        font_size_small: 12,
+        widget_styling: conrod::theme::StyleMap::default(),
+        mouse_drag_threshold: 0.0,
+        double_click_threshold: std::time::Duration::from_millis(500),
+    }
+}
+
+widget_ids! {
+    pub struct Ids {
+        canvas,
+        ip_text,
+        hostnames_text,
+        os_text,
+        os_image,
+        label_toggle,
+        edge_text,
+        edge_mode_text,
+        path_text,
+        diff_text,
+        selection_text,
+        export_button,
+        export_xml_button,
+        inventory_button,
+        group_button,
+        pin_button,
+        hide_button,
+        copy_button,
+        rescan_button,
+        rescan_command_button,
+        show_hidden_button,
+        scan_text,
+        scan_targets_box,
+        scan_profile_button,
+        scan_button,
+        rescan_canvas,
+        rescan_confirm_text,
+        rescan_confirm_button,
+        rescan_cancel_button,
+        tutorial
+    }
+}
+
+pub struct ApplicationState {
+    pub topology: Topology,
+    pub layout: Layout,
+    node_selected: Option<HostIndex>,
+    node_hovered: Option<HostIndex>,
+    /// Every selected node, including node_selected.
+    selection: HashSet<HostIndex>,
+    hidden: HashSet<HostIndex>,
+    pinned: HashMap<HostIndex, Point3<f32>>,
+    edge_selected: Option<LinkIndex>,
+    node_map: HashMap<HostIndex, SceneNode>,
+    path_nodes: HashSet<HostIndex>,
+    path_edges: HashSet<LinkIndex>,
+    selected_os_texture: Option<kiss3d::conrod::image::Id>,
+    tux_texture: image::Id,
+    puffy_texture: image::Id,
+    daemon_texture: image::Id,
+    label_nodes: bool,
+    label_mode: LabelMode,
+    label_template: Option<String>,
+    edge_mode: EdgeMode,
+    edge_arrows: bool,
+    /// Hosts arriving from a scan still being read.
+    loader: Option<Receiver<LoadMessage>>,
+    load_progress: Option<Progress>,
+    /// Hosts the loads so far found down, for the report's summary.
+    hosts_down: usize,
+    /// Where loads are recorded when they finish, if anywhere.
+    history: Option<History>,
+    /// What the current background load is reading, and the addresses of the hosts it has sent so far,
+    /// for the history.
+    load_source: String,
+    loaded_addresses: HashSet<IpAddr>,
+    /// Whether the current load is a rescan of hosts already shown, whose ports it replaces.
+    rescanning: bool,
+    /// A rescan asked for from the graph, waiting for its command line to be confirmed.
+    pending_rescan: Option<(ScanProfile, Vec<String>)>,
+    nmap_path: PathBuf,
+    /// Whitespace-separated targets typed into the scan panel.
+    scan_targets: String,
+    scan_profile: ScanProfile,
+    /// The files the topology was loaded from, including any being followed, for copying hosts out of them.
+    scan_files: Vec<PathBuf>,
+    /// Whether the topology started from the built-in sample scan rather than files or the history.
+    sample_loaded: bool,
+    /// Changes from `ndiff -x` output, overlaid on the hosts they concern.
+    diff: Option<ScanDiff>,
+    /// Where the inventory table is written; its extension picks CSV or Markdown.
+    inventory_path: PathBuf,
+}
+
+impl ApplicationState {
+    pub fn new(
+        layout: Layout,
+        topology: Topology,
+        node_map: HashMap<HostIndex, SceneNode>,
+        tux_texture: kiss3d::conrod::image::Id,
+        puffy_texture: kiss3d::conrod::image::Id,
+        daemon_texture: kiss3d::conrod::image::Id,
+    ) -> Self {
+        ApplicationState {
+            topology,
+            layout,
+            node_selected: None,
+            node_hovered: None,
+            selection: HashSet::new(),
+            hidden: HashSet::new(),
+            pinned: HashMap::new(),
+            edge_selected: None,
+            node_map,
+            path_nodes: HashSet::new(),
+            path_edges: HashSet::new(),
+            selected_os_texture: None,
+            tux_texture,
+            puffy_texture,
+            daemon_texture,
+            label_nodes: true,
+            label_mode: LabelMode::Ip,
+            label_template: None,
+            edge_mode: EdgeMode::Plain,
+            edge_arrows: false,
+            loader: None,
+            load_progress: None,
+            hosts_down: 0,
+            history: None,
+            load_source: String::new(),
+            loaded_addresses: HashSet::new(),
+            rescanning: false,
+            pending_rescan: None,
+            nmap_path: PathBuf::from(scan::DEFAULT_NMAP),
+            scan_targets: String::new(),
+            scan_profile: ScanProfile::TopPorts,
+            scan_files: Vec::new(),
+            sample_loaded: false,
+            diff: None,
+            inventory_path: PathBuf::from(DEFAULT_INVENTORY_PATH),
+        }
+    }
+
+    /// Insert the hosts the background load has read since the last frame, returning those new to the layout.
+    pub fn poll_loader(&mut self) -> Vec<HostIndex> {
+        let receiver = match &self.loader {
+            None => return Vec::new(),
+            Some(receiver) => receiver,
+        };
+        let mut finished = false;
+        let mut selection_updated = false;
+        for message in receiver.try_iter().take(LOAD_BATCH) {
+            match message {
+                LoadMessage::Host(host) => {
+                    if self.history.is_some() {
+                        self.loaded_addresses.insert(host.main_addr);
+                    }
+                    let host_index = if self.rescanning {
+                        self.topology.add_rescanned_host(host)
+                    } else {
+                        self.topology.add_scanned_host(host)
+                    };
+                    selection_updated |= self.selection.contains(&host_index);
+                }
+                LoadMessage::Progress(progress) => self.load_progress = Some(progress),
+                LoadMessage::Finished(error) => {
+                    if let Some(e) = error {
+                        eprintln!("Failed to load scan: {e}");
+                    }
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            self.loader = None;
+            let hosts_down = self
+                .load_progress
+                .take()
+                .map_or(0, |progress| progress.hosts_down);
+            self.hosts_down += hosts_down;
+            self.topology.attach_orphans();
+            self.record_load(hosts_down);
+        }
+        // A rescan that finds a new route removes the links of the old one.
+        let graph = &self.topology.graph;
+        if self
+            .edge_selected
+            .is_some_and(|link| graph.edge_weight(link).is_none())
+        {
+            self.edge_selected = None;
+        }
+        self.path_edges
+            .retain(|link| graph.edge_weight(*link).is_some());
+        // A rescan has new ports, OS guesses or routes for the hosts on show.
+        if selection_updated {
+            self.selection_changed();
+        }
+        self.layout.sync(&self.topology)
+    }
+
+    /// Store the hosts the finished load sent, as they now stand in the topology, as a scan in the history,
+    /// if one is kept.
+    fn record_load(&mut self, hosts_down: usize) {
+        let addresses = std::mem::take(&mut self.loaded_addresses);
+        let history = match self.history.as_mut() {
+            // A rescan looks again at a few hosts already shown, rather than being a scan of its own.
+            Some(history) if !self.rescanning => history,
+            _ => return,
+        };
+        let hosts: Vec<&SimpleHost> = addresses
+            .iter()
+            .filter_map(|addr| self.topology.find_host(*addr))
+            .map(|host_index| &self.topology.graph[host_index])
+            .filter(|host| host.scanned)
+            .collect();
+        match history.record_hosts(&hosts, hosts_down, &self.load_source, None) {
+            Ok(scan_id) => println!("Recorded scan {scan_id} in the history"),
+            Err(e) => eprintln!("Failed to record the scan in the history: {e}"),
+        }
+    }
+
+    /// Start reading hosts from a background load, described by `source` in the history.
+    fn start_load(&mut self, loader: Receiver<LoadMessage>, source: String) {
+        self.loader = Some(loader);
+        self.load_source = source;
+        self.loaded_addresses.clear();
+        self.rescanning = false;
+    }
+
+    /// Run nmap on the targets in the scan panel, growing the graph as it reports hosts.
+    fn start_scan(&mut self) {
+        let targets: Vec<String> = self
+            .scan_targets
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        match scan::spawn_scan(&self.nmap_path, self.scan_profile, &targets) {
+            Ok(messages) => self.start_load(
+                messages,
+                format!("{} scan of {}", self.scan_profile.name(), targets.join(" ")),
+            ),
+            Err(e) => eprintln!("Failed to start {}: {e}", self.nmap_path.display()),
+        }
+    }
+
+    /// The hosts a rescan from the node covers: the selection if the node is part of it, otherwise the node alone.
+    /// Groups are rescanned member by member.
+    fn rescan_targets(&self, host_index: HostIndex) -> Vec<String> {
+        let mut hosts: Vec<HostIndex> = if self.selection.contains(&host_index) {
+            self.selection.iter().copied().collect()
+        } else {
+            vec![host_index]
+        };
+        let members: Vec<HostIndex> = hosts
+            .iter()
+            .filter_map(|n| self.topology.group_members(*n))
+            .flatten()
+            .copied()
+            .collect();
+        hosts.extend(members);
+        hosts.sort();
+        hosts.dedup();
+        hosts
+            .iter()
+            .filter(|n| **n != Topology::root())
+            .map(|n| self.topology.graph[*n].main_addr.to_string())
+            .collect()
+    }
+
+    /// Scan the hosts again one profile deeper than the scan panel's, merging the results into the graph.
+    pub fn rescan(&mut self, host_index: HostIndex) {
+        let targets = self.rescan_targets(host_index);
+        self.start_rescan(self.scan_profile.deeper(), targets);
+    }
+
+    /// Ask to rescan from the node, showing the nmap command line before anything is run.
+    pub fn confirm_rescan(&mut self, host_index: HostIndex) {
+        let targets = self.rescan_targets(host_index);
+        if !targets.is_empty() {
+            self.pending_rescan = Some((self.scan_profile.deeper(), targets));
+        }
+    }
+
+    /// Run the profile over the targets, replacing what is known of their ports with what it finds.
+    fn start_rescan(&mut self, profile: ScanProfile, targets: Vec<String>) {
+        if self.loader.is_some() {
+            eprintln!("Wait for the current scan to finish before rescanning.");
+            return;
+        }
+        if targets.is_empty() {
+            return;
+        }
+        match scan::spawn_scan(&self.nmap_path, profile, &targets) {
+            Ok(messages) => {
+                self.start_load(
+                    messages,
+                    format!("{} rescan of {}", profile.name(), targets.join(" ")),
+                );
+                self.rescanning = true;
+            }
+            Err(e) => eprintln!("Failed to start {}: {e}", self.nmap_path.display()),
+        }
+    }
+
+    /// Write the rescan's targets to RESCAN_TARGETS_PATH and print the nmap command that scans them.
+    pub fn save_rescan_command(
+        &self,
+        host_index: HostIndex,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let targets = self.rescan_targets(host_index);
+        scan::write_target_list(&targets, Path::new(RESCAN_TARGETS_PATH))?;
+        println!(
+            "{}",
+            scan::command_line(
+                &self.nmap_path,
+                self.scan_profile.deeper(),
+                Path::new(RESCAN_TARGETS_PATH),
+                Path::new(RESCAN_OUTPUT_PATH),
+            )
+        );
+        Ok(())
+    }
+
+    /// Select the nearest node under the ray, falling back to the nearest edge if no node is hit.
+    /// If additive, toggles the node in the current selection instead.
+    pub fn select_nearest_intersection(
+        &mut self,
+        ray_origin: Point3<f32>,
+        ray_direction: Vector3<f32>,
+        additive: bool,
+    ) {
+        let int = self.find_nearest_intersection(ray_origin, ray_direction);
+        if additive {
+            if let Some(node_index) = int {
+                self.toggle_selected_node(node_index);
+            }
+            return;
+        }
+        self.set_selected_node(int);
+        self.edge_selected = match int {
+            Some(_) => None,
+            None => self.find_nearest_edge(ray_origin, ray_direction),
+        };
+    }
+
+    /// Select every visible node in front of the camera whose projection lies within the screen-space rectangle.
+    pub fn select_in_rectangle(
+        &mut self,
+        corner_a: &Point2<f32>,
+        corner_b: &Point2<f32>,
+        camera: &ArcBall,
+        window_size: &Vector2<f32>,
+        additive: bool,
+    ) {
+        let (min_x, max_x) = (corner_a.x.min(corner_b.x), corner_a.x.max(corner_b.x));
+        let (min_y, max_y) = (corner_a.y.min(corner_b.y), corner_a.y.max(corner_b.y));
+        let eye = camera.eye();
+        let view_direction = camera.at() - eye;
+
+        let inside: Vec<HostIndex> = self
+            .topology
+            .host_indices()
+            .filter(|node_index| self.is_visible(*node_index))
+            .filter(|node_index| {
+                let location = self.node_location(*node_index);
+                if (location - eye).dot(&view_direction) <= 0.0 {
+                    return false;
+                }
+                let projection = camera.project(&location, window_size);
+                let (x, y) = (projection.x, window_size.y - projection.y);
+                (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+            })
+            .collect();
+
+        if !additive {
+            self.selection.clear();
+            self.node_selected = None;
+        }
+        self.selection.extend(inside.iter().copied());
+        if self.node_selected.is_none() {
+            self.node_selected = inside.first().copied();
+        }
+        self.edge_selected = None;
+        self.selection_changed();
+    }
+
+    /// Add the node to the selection, or remove it if already selected.
+    pub fn toggle_selected_node(&mut self, node_index: HostIndex) {
+        if self.selection.remove(&node_index) {
+            if self.node_selected == Some(node_index) {
+                self.node_selected = self.selection.iter().next().copied();
+            }
+        } else {
+            self.selection.insert(node_index);
+            self.node_selected = Some(node_index);
+        }
+        self.edge_selected = None;
+        self.selection_changed();
+    }
+
+    /// Return whether the node is neither hidden nor folded into a collapsed group.
+    pub fn is_visible(&self, node_index: HostIndex) -> bool {
+        !self.hidden.contains(&node_index) && self.topology.grouped_into(node_index).is_none()
+    }
+
+    /// Return the node drawn in place of the given one: itself, its group, or nothing if hidden.
+    fn displayed_node(&self, node_index: HostIndex) -> Option<HostIndex> {
+        let shown = self.topology.grouped_into(node_index).unwrap_or(node_index);
+        if self.hidden.contains(&node_index) || self.hidden.contains(&shown) {
+            None
+        } else {
+            Some(shown)
+        }
+    }
+
+    /// Return how strongly a node's label should be kept: selected or hovered, on a highlighted path or pinned, well connected, or none of these.
+    fn label_priority(&self, node_index: HostIndex) -> u8 {
+        if self.selection.contains(&node_index) || self.node_hovered == Some(node_index) {
+            3
+        } else if self.path_nodes.contains(&node_index) || self.pinned.contains_key(&node_index) {
+            2
+        } else if self.topology.degree(node_index) >= HIGH_DEGREE {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Draw labels in front of the camera, highest priority and nearest first, skipping any that would overlap a label already drawn.
+    /// Ordinary labels fade with distance and are culled beyond LABEL_CULL_DISTANCE.
+    pub fn draw_labels(
+        &self,
+        window: &mut Window,
+        camera: &ArcBall,
+        window_size: &Vector2<f32>,
+        font: &Rc<kiss3d::text::Font>,
+    ) {
+        if !self.label_nodes {
+            return;
+        }
+
+        let eye = camera.eye();
+        let view_direction = camera.at() - eye;
+        let mut candidates = Vec::new();
+        for node_index in self.topology.host_indices() {
+            if !self.is_visible(node_index) {
+                continue;
+            }
+            let offset = self.node_location(node_index) - eye;
+            // Behind the viewer, projection mirrors the node onto the screen.
+            if offset.dot(&view_direction) <= 0.0 {
+                continue;
+            }
+            let distance = offset.norm();
+            let priority = self.label_priority(node_index);
+            if priority == 0 && distance > LABEL_CULL_DISTANCE {
+                continue;
+            }
+            candidates.push((priority, distance, node_index));
+        }
+        candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.total_cmp(&b.1)));
+
+        let mut placer = LabelPlacer::default();
+        for (priority, distance, node_index) in candidates {
+            let label = match self.get_label(node_index) {
+                None => continue,
+                Some(label) => label,
+            };
+            let projection = camera.project(&self.node_location(node_index), window_size);
+            let screen_position =
+                Point2::new(2.0 * projection.x, 2.0 * (window_size.y - projection.y));
+            let width = label.chars().count() as f32 * LABEL_CHAR_WIDTH;
+            if !placer.try_place(screen_position.x, screen_position.y, width, LABEL_SIZE) {
+                continue;
+            }
+
+            let fade = if priority > 0 {
+                1.0
+            } else {
+                1.0 - ((distance - LABEL_FADE_DISTANCE)
+                    / (LABEL_CULL_DISTANCE - LABEL_FADE_DISTANCE))
+                    .clamp(0.0, 1.0)
+            };
+            window.draw_text(
+                label.as_str(),
+                &screen_position,
+                LABEL_SIZE,
+                font,
+                &Point3::from(TEXT_COLOR.coords * fade),
+            );
+        }
+    }
+
+    /// Return the label text for a visible node.
+    pub fn get_label(&self, node_index: HostIndex) -> Option<String> {
+        if !self.is_visible(node_index) {
+            return None;
+        }
+        let label = labels::format_label(&self.topology.graph[node_index], &self.label_mode);
+        match self.topology.group_members(node_index) {
+            Some(members) => Some(format!("{label} (+{})", members.len())),
+            None => Some(label),
+        }
+    }
+
+    /// Return the lines of the tooltip for the node under the cursor, if any.
+    pub fn get_tooltip(&self) -> Vec<String> {
+        let node_index = match self.node_hovered {
+            None => return Vec::new(),
+            Some(n) => n,
+        };
+        let host = &self.topology.graph[node_index];
+
+        let hostname = host
+            .main_hostname
+            .as_ref()
+            .map_or("-".to_string(), |name| name.to_string());
+        let os_family = host.os_guess.as_ref().map_or("unknown", |os| os.family());
+
+        let open_ports: Vec<String> = host.open_ports().map(|port| port.to_string()).collect();
+        let ports = if open_ports.is_empty() {
+            "none".to_string()
+        } else if open_ports.len() > TOOLTIP_PORTS {
+            format!(
+                "{} (+{} more)",
+                open_ports[..TOOLTIP_PORTS].join(", "),
+                open_ports.len() - TOOLTIP_PORTS
+            )
+        } else {
+            open_ports.join(", ")
+        };
+
+        vec![
+            host.main_addr.to_string(),
+            format!("Hostname: {hostname}"),
+            format!("OS: {os_family}"),
+            format!("Open ports: {ports}"),
+        ]
+    }
+
+    /// Copy the selected hosts, and the members of selected groups, out of the nmap XML input holding all of
+    /// them into SELECTION_XML_PATH, returning how many were written. Hosts found by live scans have no XML
+    /// to copy, so selecting any of them is an error rather than a silently shorter file.
+    pub fn export_selection_xml(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut addresses = HashSet::new();
+        for host_index in &self.selection {
+            let members = self.topology.group_members(*host_index).unwrap_or_default();
+            for member in std::iter::once(host_index).chain(members) {
+                // Traceroute hops and the scanner are not hosts of any scan.
+                let host = &self.topology.graph[*member];
+                if host.scanned {
+                    addresses.insert(host.main_addr);
+                }
+            }
+        }
+        if addresses.is_empty() {
+            return Err("No scanned hosts are selected.".into());
+        }
+
+        let mut sources: Vec<Option<&Path>> = self
+            .scan_files
+            .iter()
+            .map(|path| Some(path.as_path()))
+            .collect();
+        if self.sample_loaded {
+            sources.push(None);
+        }
+        let mut found = HashSet::new();
+        for path in sources {
+            let input = import::open_input(path)?;
+            if input.format != InputFormat::NmapXml {
+                continue;
+            }
+            let mut kept_addresses = HashSet::new();
+            let mut buffer = Vec::new();
+            let kept = subset::write_subset(input.reader, &mut buffer, |host| {
+                let keep = addresses.contains(&host.main_addr);
+                if keep {
+                    kept_addresses.insert(host.main_addr);
+                }
+                keep
+            })?;
+            if kept_addresses.len() == addresses.len() {
+                fs::write(SELECTION_XML_PATH, buffer)?;
+                return Ok(kept);
+            }
+            found.extend(kept_addresses);
+        }
+
+        let mut missing: Vec<String> = addresses
+            .difference(&found)
+            .map(|addr| addr.to_string())
+            .collect();
+        if missing.is_empty() {
+            return Err("The selected hosts are spread over several nmap XML scans.".into());
+        }
+        missing.sort();
+        Err(format!(
+            "No loaded nmap XML scan holds {}; hosts from live scans can only be saved as XML by nmap itself.",
+            missing.join(", ")
+        )
+        .into())
+    }
+
+    /// Write the hosts that are not hidden, or only the selected hosts and the members of selected groups,
+    /// to inventory_path, returning how many were written.
+    pub fn export_inventory(
+        &self,
+        selected_only: bool,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let rows = inventory::inventory(&self.topology, |host_index, _| {
+            if selected_only {
+                self.selection.contains(&host_index)
+                    || self
+                        .topology
+                        .grouped_into(host_index)
+                        .is_some_and(|representative| self.selection.contains(&representative))
+            } else {
+                self.displayed_node(host_index).is_some()
+            }
+        });
+        inventory::save_inventory(&rows, &self.inventory_path)?;
+        Ok(rows.len())
+    }
+
+    /// Return the selected hosts in index order.
+    pub fn get_selected_hosts(&self) -> Vec<&SimpleHost> {
+        let mut indices: Vec<HostIndex> = self.selection.iter().copied().collect();
+        indices.sort();
+        indices.iter().map(|n| &self.topology.graph[*n]).collect()
+    }
+
+    /// Write the selected hosts to SELECTION_EXPORT_PATH as JSON.
+    pub fn export_selection(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let hosts: Vec<serde_json::Value> = self
+            .get_selected_hosts()
+            .iter()
+            .map(|host| {
+                serde_json::json!({
+                    "address": host.main_addr.to_string(),
+                    "hostname": host.main_hostname.as_ref().map(|name| name.to_string()),
+                    "os": host.os_guess.as_ref().map(|os| os.name().to_string()),
+                    "rtt": host.rtt,
+                })
+            })
+            .collect();
+        fs::write(SELECTION_EXPORT_PATH, serde_json::to_string_pretty(&hosts)?)?;
+        Ok(())
+    }
+
+    /// Copy the selected addresses to the clipboard, one per line.
+    pub fn copy_selected_addresses(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let addresses: Vec<String> = self
+            .get_selected_hosts()
+            .iter()
+            .map(|host| host.main_addr.to_string())
+            .collect();
+        copy_to_clipboard(&addresses.join("\n"))
+    }
+
+    /// Fold the selection into the selected node, which is drawn enlarged in place of the group.
+    pub fn collapse_selection(&mut self) {
+        let representative = match self.node_selected {
+            None => return,
+            Some(n) => n,
+        };
+        let members = self
+            .topology
+            .group(representative, self.selection.iter().copied());
+        if members.is_empty() {
+            return;
+        }
+
+        for member in &members {
+            self.node_map.get_mut(member).unwrap().set_visible(false);
+        }
+        self.node_map
+            .get_mut(&representative)
+            .unwrap()
+            .set_local_scale(GROUP_SCALE, GROUP_SCALE, GROUP_SCALE);
+        self.set_selected_node(Some(representative));
+    }
+
+    /// Restore the members of the group the node stands in for.
+    pub fn expand_group(&mut self, representative: HostIndex) {
+        if !self.topology.is_group(representative) {
+            return;
+        }
+        for member in &self.topology.ungroup(representative) {
+            if !self.hidden.contains(member) {
+                self.node_map.get_mut(member).unwrap().set_visible(true);
+            }
+        }
+        self.node_map
+            .get_mut(&representative)
+            .unwrap()
+            .set_local_scale(1.0, 1.0, 1.0);
+    }
+
+    /// Pin the selection in place, or unpin it if it is already entirely pinned.
+    pub fn toggle_pin_selection(&mut self) {
+        if self.is_selection_pinned() {
+            for node_index in &self.selection {
+                self.pinned.remove(node_index);
+            }
+        } else {
+            for node_index in self.selection.iter().copied() {
+                let location = self.node_location(node_index);
+                self.pinned.insert(node_index, location);
+            }
+        }
+    }
+
+    fn is_selection_pinned(&self) -> bool {
+        self.selection
+            .iter()
+            .all(|node_index| self.pinned.contains_key(node_index))
+    }
+
+    /// Move pinned nodes back to where they were pinned, undoing the last simulation step for them.
+    pub fn apply_pins(&mut self) {
+        for (node_index, location) in &self.pinned {
+            self.layout
+                .set_position(*node_index, [location.x, location.y, location.z]);
+        }
+    }
+
+    /// Hide the selected nodes and their edges, and clear the selection.
+    pub fn hide_selection(&mut self) {
+        for node_index in self.selection.iter() {
+            self.node_map
+                .get_mut(node_index)
+                .unwrap()
+                .set_visible(false);
+        }
+        self.hidden.extend(self.selection.iter().copied());
+        self.set_selected_node(None);
+    }
+
+    /// Unhide every hidden node, leaving collapsed group members folded away.
+    pub fn show_hidden(&mut self) {
+        for node_index in self.hidden.drain() {
+            if self.topology.grouped_into(node_index).is_none() {
+                self.node_map
+                    .get_mut(&node_index)
+                    .unwrap()
+                    .set_visible(true);
+            }
+        }
+    }
+
+    fn node_location(&self, node_index: HostIndex) -> Point3<f32> {
+        let [x, y, z] = self.layout.position(node_index);
+        Point3::new(x, y, z)
+    }
+
+    /// Draw every edge once, styled according to the current edge mode.
+    pub fn draw_edges(&self, window: &mut Window, eye: &Point3<f32>) {
+        let graph = &self.topology.graph;
+
+        let max_rtt_delta = graph
+            .edge_indices()
+            .filter_map(|edge_index| graph[edge_index].rtt_delta)
+            .fold(0.0f32, f32::max);
+        // Edges added without a traceroute carry no traces, so the largest count may be zero.
+        let max_traces = graph
+            .edge_indices()
+            .map(|edge_index| graph[edge_index].traces.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        // Arrows point away from the scanner, from the end of each link nearer to it.
+        let depths = if self.edge_arrows {
+            stats::breadth_first_depths(graph, Topology::root())
+        } else {
+            HashMap::new()
+        };
+
+        // Edges of collapsed groups are redrawn from the group, so several may coincide.
+        let mut drawn = HashSet::new();
+        for edge_index in graph.edge_indices() {
+            let (link_source, link_target) = graph.edge_endpoints(edge_index).unwrap();
+            let (source, target) = match (
+                self.displayed_node(link_source),
+                self.displayed_node(link_target),
+            ) {
+                (Some(source), Some(target)) if source != target => (source, target),
+                _ => continue,
+            };
+            if !drawn.insert((source.min(target), source.max(target))) {
+                continue;
+            }
+            let link = &graph[edge_index];
+            let start = self.node_location(source);
+            let end = self.node_location(target);
+            let direction = end - start;
+            if direction.norm() < f32::EPSILON {
+                continue;
+            }
+            // Perpendicular to both the edge and the line of sight, so offsets stay visible.
+            let side = direction
+                .cross(&(*eye - start))
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::zeros);
+
+            let color = if self.edge_selected == Some(edge_index) {
+                SELECTED_LINE_COLOR
+            } else if self.path_edges.contains(&edge_index) {
+                PATH_LINE_COLOR
+            } else if !self.path_edges.is_empty() {
+                FADED_LINE_COLOR
+            } else if self.edge_mode == EdgeMode::RttDelta {
+                let fraction = match link.rtt_delta {
+                    Some(delta) if max_rtt_delta > 0.0 => (delta / max_rtt_delta).clamp(0.0, 1.0),
+                    _ => 0.0,
+                };
+                Point3::from(LINE_COLOR.coords.lerp(&SLOW_LINE_COLOR.coords, fraction))
+            } else {
+                LINE_COLOR
+            };
+
+            let strands = if self.edge_mode == EdgeMode::TraceCount {
+                1 + (MAX_EDGE_STRANDS - 1) * link.traces.len() / max_traces
+            } else {
+                1
+            };
+            for strand in 0..strands {
+                let offset =
+                    side * EDGE_STRAND_SPACING * (strand as f32 - (strands - 1) as f32 / 2.0);
+                window.draw_line(&(start + offset), &(end + offset), &color);
+            }
+
+            if self.edge_arrows {
+                // Links out of reach of the scanner, or between hosts as far from it, keep their own order.
+                let inward = matches!(
+                    (depths.get(&link_source), depths.get(&link_target)),
+                    (Some(source_depth), Some(target_depth)) if target_depth < source_depth
+                );
+                let (from, to) = if inward { (end, start) } else { (start, end) };
+                let unit = (to - from).normalize();
+                let tip = to - unit * NODE_RADIUS;
+                let base = tip - unit * ARROW_LENGTH;
+                window.draw_line(&tip, &(base + side * ARROW_WIDTH), &color);
+                window.draw_line(&tip, &(base - side * ARROW_WIDTH), &color);
+            }
+        }
+    }
+
+    /// Return a description of the selected edge's endpoints and the traces using it, if one is selected.
+    pub fn get_selected_edge_description(&self) -> Option<String> {
+        let edge_index = self.edge_selected?;
+        let graph = &self.topology.graph;
+        let (source, target) = graph.edge_endpoints(edge_index)?;
+        let link = &graph[edge_index];
+
+        let mut description = format!(
+            "Edge: {} -> {}\n",
+            graph[source].main_addr, graph[target].main_addr
+        );
+        if let Some(delta) = link.rtt_delta {
+            description.push_str(&format!("RTT delta: {delta:.2} ms\n"));
+        }
+        description.push_str(&format!("Traces ({}):\n", link.traces.len()));
+        for trace in &link.traces {
+            description.push_str(&format!("{trace}\n"));
+        }
+        Some(description)
+    }
+
+    /// Return the required IP for the current selected node, if it exists.
+    pub fn get_selected_ip(&self) -> Option<String> {
+        self.node_selected.map(|n| {
+            self.topology
+                .graph
+                .node_weight(n)
+                .unwrap()
+                .main_addr
+                .to_string()
+        })
+    }
+
+    /// Return every known hostname of the selected node with its type, one per line.
+    pub fn get_selected_hostnames(&self) -> Option<String> {
+        let host = &self.topology.graph[self.node_selected?];
+        if host.hostnames.is_empty() {
+            return Some("No known hostnames".to_string());
+        }
+
+        let mut description = "Hostnames:\n".to_string();
+        for hostname in &host.hostnames {
+            match &hostname.kind {
+                Some(kind) => description.push_str(&format!("{} ({kind})\n", hostname.name)),
+                None => description.push_str(&format!("{}\n", hostname.name)),
+            }
+        }
+        Some(description)
+    }
+
+    /// Return the optional OsGuess for the current selected node, if it exists.
+    pub fn get_selected_os(&self) -> Option<Option<OsGuess>> {
+        match self.node_selected {
+            None => None,
+            Some(n) => {
+                let guess = self.topology.graph.node_weight(n).unwrap().os_guess.clone();
+                Some(guess)
+            }
+        }
+    }
+
+    pub fn set_os_texture(&mut self) {
+        self.selected_os_texture = match self.get_selected_os() {
+            None => None,
+            Some(guess_option) => match guess_option {
+                None => None,
+                Some(guess) => match guess {
+                    OsGuess::Linux(_) => Some(self.tux_texture),
+                    OsGuess::Freebsd(_) => Some(self.daemon_texture),
+                    OsGuess::Openbsd(_) => Some(self.puffy_texture),
+                    OsGuess::Other(_) => None,
+                },
+            },
+        };
+    }
+
+    /// Set the selected node(index) to the given one. Paints scene nodes accordingly.
+    pub fn set_selected_node(&mut self, selected_node: Option<HostIndex>) {
+        self.node_selected = selected_node;
+        self.selection = selected_node.into_iter().collect();
+        self.selection_changed();
+    }
+
+    /// Refresh everything derived from the selection.
+    fn selection_changed(&mut self) {
+        self.set_path_highlight();
+        self.paint_nodes();
+        self.set_os_texture();
+    }
+
+    /// Return the traceroute paths from the scanner root to the selected node, one per distinct route.
+    /// Routers have no trace of their own, so their paths are the prefixes of the traces crossing them.
+    pub fn get_selected_paths(&self) -> Vec<Vec<SimpleHop>> {
+        match self.node_selected {
+            None => Vec::new(),
+            Some(n) => self.topology.traces_to(n),
+        }
+    }
+
+    /// Recompute the nodes and edges lying on the paths to the selected node.
+    fn set_path_highlight(&mut self) {
+        let mut path_nodes = HashSet::new();
+        let mut path_edges = HashSet::new();
+
+        for path in self.get_selected_paths() {
+            let (hosts, links) = self.topology.trace_route(&path);
+            path_nodes.extend(hosts);
+            path_edges.extend(links);
+        }
+
+        self.path_nodes = path_nodes;
+        self.path_edges = path_edges;
+    }
+
+    /// Paint every scene node according to the selection and highlighted paths.
+    fn paint_nodes(&mut self) {
+        for (node_index, scene_node) in self.node_map.iter_mut() {
+            if self.selection.contains(node_index) {
+                scene_node.paint_selected();
+            } else if self.path_nodes.contains(node_index) {
+                scene_node.paint_path();
+            } else if !self.path_nodes.is_empty() {
+                scene_node.paint_faded();
+            } else if let Some(host_diff) = self.host_diff(*node_index) {
+                scene_node.paint_change(host_diff.change);
+            } else {
+                scene_node.paint_default();
+            }
+        }
+    }
+
+    fn host_diff(&self, host_index: HostIndex) -> Option<&HostDiff> {
+        self.diff
+            .as_ref()?
+            .host(self.topology.graph[host_index].main_addr)
+    }
+
+    /// Return the ndiff entries for the selected node, if the overlay has any.
+    pub fn get_selected_diff_description(&self) -> Option<String> {
+        let host_diff = self.host_diff(self.node_selected?)?;
+        Some(format!("Changes:\n{}", host_diff.entries().join("\n")))
+    }
+
+    /// Return one line per hop on each path to the selected node, if any exist.
+    pub fn get_selected_path_description(&self) -> Option<String> {
+        let paths = self.get_selected_paths();
+        if paths.is_empty() {
+            return None;
+        }
+
+        let mut description = String::new();
+        for (number, path) in paths.iter().enumerate() {
+            description.push_str(&format!("Path {}:\n", number + 1));
+            for hop in path {
+                let ttl = hop.ttl.map_or("?".to_string(), |ttl| ttl.to_string());
+                let hostname = hop.hostname.as_deref().unwrap_or("-");
+                let rtt = hop
+                    .rtt
+                    .map_or("--".to_string(), |rtt| format!("{rtt:.2} ms"));
+                description.push_str(&format!("{ttl}. {} {hostname} {rtt}", hop.addr));
+                if let Some(loss) = hop.loss {
+                    description.push_str(&format!(" {loss:.0}% loss"));
+                }
+                if let Some(jitter) = hop.jitter {
+                    description.push_str(&format!(" jitter {jitter:.2} ms"));
+                }
+                description.push('\n');
+            }
+        }
+        Some(description)
+    }
+
+    pub fn gui(&mut self, ui: &mut conrod::UiCell, ids: &Ids) {
+        use conrod::{widget, Labelable, Sizeable, Widget};
+
+        const MARGIN: conrod::Scalar = 10.0;
+
+        widget::Canvas::new()
+            .pad(MARGIN)
+            .align_right()
+            .w(200.0)
+            .scroll_kids_vertically()
+            .set(ids.canvas, ui);
+
+        if self.node_selected.is_some() {
+            let ip = self.get_selected_ip();
+            if ip.is_some() {
+                widget::Text::new(format!("IP: {}", ip.as_ref().unwrap()).as_str())
+                    .mid_top_of(ids.canvas)
+                    .align_middle_x_of(ids.canvas)
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .h(40.0)
+                    .set(ids.tutorial, ui);
+            }
+            if let Some(hostnames) = self.get_selected_hostnames() {
+                widget::Text::new(hostnames.as_str())
+                    .align_middle_x_of(ids.canvas)
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .down(10.0)
+                    .set(ids.hostnames_text, ui);
+            }
+            let os = self.get_selected_os();
+            if os.is_some() && os.as_ref().unwrap().is_some() {
+                let os_string = match os.as_ref().unwrap().as_ref().unwrap() {
+                    // seems required, unfortunately
+                    OsGuess::Linux(string)
+                    | OsGuess::Freebsd(string)
+                    | OsGuess::Openbsd(string)
+                    | OsGuess::Other(string) => string,
+                };
+                widget::Text::new(format!("OS: {os_string}").as_str())
+                    .mid_top_of(ids.canvas)
+                    .align_middle_x_of(ids.canvas)
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .h(40.0)
+                    .down(20.0)
+                    .set(ids.os_text, ui);
+            }
+            if self.selected_os_texture.is_some() {
+                widget::Image::new(self.selected_os_texture.unwrap())
+                    .w_h(144.0, 144.0)
+                    .down(40.0)
+                    .align_middle_x_of(ids.canvas)
+                    .set(ids.os_image, ui);
+            }
+            if let Some(description) = self.get_selected_path_description() {
+                widget::Text::new(description.as_str())
+                    .align_middle_x_of(ids.canvas)
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .down(20.0)
+                    .set(ids.path_text, ui);
+            }
+            if let Some(description) = self.get_selected_diff_description() {
+                widget::Text::new(description.as_str())
+                    .align_middle_x_of(ids.canvas)
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .down(20.0)
+                    .set(ids.diff_text, ui);
+            }
+        } else if let Some(description) = self.get_selected_edge_description() {
+            widget::Text::new(description.as_str())
+                .mid_top_of(ids.canvas)
+                .align_middle_x_of(ids.canvas)
+                .padded_w_of(ids.canvas, MARGIN)
+                .set(ids.edge_text, ui);
+        } else {
+            widget::Text::new("Select a node or edge with Ctrl+LMB to learn more about it.\nShift+LMB adds to the selection.\nDrag with Ctrl or Shift held to box select.\nHold LMB to rotate.\nHold RMB to pan.\nRight-click a node to rescan it or the selection.\nScroll to zoom.\nPress E to cycle edge modes.\nPress A to toggle edge arrows.\nPress L to cycle label modes.\nPress N to toggle labels.\nPress X to export the topology.\nPress I to save the host inventory.\nPress R to save an HTML report.\nPress K to record a camera keyframe, Shift+K to drop the last.")
+                .mid_top_of(ids.canvas)
+                .align_middle_x_of(ids.canvas)
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(40.0)
+                .set(ids.tutorial, ui);
+            widget::Text::new(
+                format!(
+                    "Edge mode: {}\nLabel mode: {}",
+                    self.edge_mode.name(),
+                    self.label_mode.name()
+                )
+                .as_str(),
+            )
+            .align_middle_x_of(ids.canvas)
+            .padded_w_of(ids.canvas, MARGIN)
+            .down(80.0)
+            .set(ids.edge_mode_text, ui);
+        }
+
+        if !self.selection.is_empty() {
+            widget::Text::new(format!("{} selected", self.selection.len()).as_str())
+                .align_middle_x_of(ids.canvas)
+                .padded_w_of(ids.canvas, MARGIN)
+                .down(20.0)
+                .set(ids.selection_text, ui);
+
+            if widget::Button::new()
+                .label("Export")
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.export_button, ui)
+                .was_clicked()
+            {
+                match self.export_selection() {
+                    Ok(()) => println!("Exported selection to {SELECTION_EXPORT_PATH}"),
+                    Err(e) => eprintln!("Failed to export selection: {e}"),
+                }
+            }
+
+            if widget::Button::new()
+                .label("Save as nmap XML")
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.export_xml_button, ui)
+                .was_clicked()
+            {
+                match self.export_selection_xml() {
+                    Ok(kept) => println!("Wrote {kept} hosts to {SELECTION_XML_PATH}"),
+                    Err(e) => eprintln!("Failed to write selection as nmap XML: {e}"),
+                }
+            }
+
+            if widget::Button::new()
+                .label("Save inventory")
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.inventory_button, ui)
+                .was_clicked()
+            {
+                match self.export_inventory(true) {
+                    Ok(count) => {
+                        println!("Wrote {count} hosts to {}", self.inventory_path.display())
+                    }
+                    Err(e) => eprintln!("Failed to write inventory: {e}"),
+                }
+            }
+
+            let group_label = match self.node_selected {
+                Some(n) if self.topology.is_group(n) => "Expand group",
+                _ => "Collapse into group",
+            };
+            if widget::Button::new()
+                .label(group_label)
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.group_button, ui)
+                .was_clicked()
+            {
+                match self.node_selected {
+                    Some(n) if self.topology.is_group(n) => self.expand_group(n),
+                    _ => self.collapse_selection(),
+                }
+            }
+
+            let pin_label = if self.is_selection_pinned() {
+                "Unpin"
+            } else {
+                "Pin"
+            };
+            if widget::Button::new()
+                .label(pin_label)
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.pin_button, ui)
+                .was_clicked()
+            {
+                self.toggle_pin_selection();
+            }
+
+            if widget::Button::new()
+                .label("Hide")
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.hide_button, ui)
+                .was_clicked()
+            {
+                self.hide_selection();
+            }
+
+            if let Some(n) = self.node_selected {
+                if widget::Button::new()
+                    .label(format!("Rescan ({})", self.scan_profile.deeper().name()).as_str())
+                    .enabled(self.loader.is_none())
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .h(30.0)
+                    .down(10.0)
+                    .set(ids.rescan_button, ui)
+                    .was_clicked()
+                {
+                    self.rescan(n);
+                }
+
+                if widget::Button::new()
+                    .label("Save nmap command")
+                    .padded_w_of(ids.canvas, MARGIN)
+                    .h(30.0)
+                    .down(10.0)
+                    .set(ids.rescan_command_button, ui)
+                    .was_clicked()
+                {
+                    match self.save_rescan_command(n) {
+                        Ok(()) => println!("Saved rescan targets to {RESCAN_TARGETS_PATH}"),
+                        Err(e) => eprintln!("Failed to save rescan targets: {e}"),
+                    }
+                }
+            }
+
+            if widget::Button::new()
+                .label("Copy addresses")
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.copy_button, ui)
+                .was_clicked()
+            {
+                if let Err(e) = self.copy_selected_addresses() {
+                    eprintln!("Failed to copy addresses: {e}");
+                }
+            }
+        }
+
+        if !self.hidden.is_empty()
+            && widget::Button::new()
+                .label(format!("Show {} hidden", self.hidden.len()).as_str())
+                .padded_w_of(ids.canvas, MARGIN)
+                .h(30.0)
+                .down(10.0)
+                .set(ids.show_hidden_button, ui)
+                .was_clicked()
+        {
+            self.show_hidden();
+        }
+
+        widget::Text::new("Scan targets:")
+            .align_middle_x_of(ids.canvas)
+            .padded_w_of(ids.canvas, MARGIN)
+            .down(20.0)
+            .set(ids.scan_text, ui);
+        for event in widget::TextBox::new(&self.scan_targets)
+            .padded_w_of(ids.canvas, MARGIN)
+            .h(30.0)
+            .down(10.0)
+            .set(ids.scan_targets_box, ui)
+        {
+            match event {
+                widget::text_box::Event::Update(targets) => self.scan_targets = targets,
+                widget::text_box::Event::Enter => {
+                    if self.loader.is_none() {
+                        self.start_scan();
+                    }
+                }
+            }
+        }
+        if widget::Button::new()
+            .label(format!("Profile: {}", self.scan_profile.name()).as_str())
+            .padded_w_of(ids.canvas, MARGIN)
+            .h(30.0)
+            .down(10.0)
+            .set(ids.scan_profile_button, ui)
+            .was_clicked()
+        {
+            self.scan_profile = self.scan_profile.next();
+        }
+        // One load at a time, so a scan waits for the current one to finish.
+        let scan_label = if self.loader.is_some() {
+            "Loading..."
+        } else {
+            "Scan"
+        };
+        if widget::Button::new()
+            .label(scan_label)
+            .enabled(self.loader.is_none())
+            .padded_w_of(ids.canvas, MARGIN)
+            .h(30.0)
+            .down(10.0)
+            .set(ids.scan_button, ui)
+            .was_clicked()
+        {
+            self.start_scan();
+        }
+
+        if let Some((profile, targets)) = self.pending_rescan.clone() {
+            widget::Canvas::new()
+                .pad(MARGIN)
+                .w_h(420.0, 160.0)
+                .top_left_with_margin(MARGIN)
+                .set(ids.rescan_canvas, ui);
+            let command = scan::live_command_line(&self.nmap_path, profile, &targets);
+            widget::Text::new(format!("Rescan with:\n{command}").as_str())
+                .top_left_of(ids.rescan_canvas)
+                .padded_w_of(ids.rescan_canvas, MARGIN)
+                .h(80.0)
+                .set(ids.rescan_confirm_text, ui);
+            if widget::Button::new()
+                .label("Run")
+                .enabled(self.loader.is_none())
+                .w_h(120.0, 30.0)
+                .down(10.0)
+                .set(ids.rescan_confirm_button, ui)
+                .was_clicked()
+            {
+                self.pending_rescan = None;
+                self.start_rescan(profile, targets);
+            }
+            if widget::Button::new()
+                .label("Cancel")
+                .w_h(120.0, 30.0)
+                .right(10.0)
+                .set(ids.rescan_cancel_button, ui)
+                .was_clicked()
+            {
+                self.pending_rescan = None;
+            }
+        }
+    }
+
+    /// Given a ray with origin and direction, find the nearest node (modeled as a sphere centered on its layout position) intersecting the ray, if it exists.
+    /// Ignores negative-distance intersections.
+    pub fn find_nearest_intersection(
+        &self,
+        ray_origin: Point3<f32>,
+        ray_direction: Vector3<f32>,
+    ) -> Option<HostIndex> {
+        let radius = NODE_RADIUS;
+
+        let mut least_distance = f32::MAX;
+        let mut nearest_node: Option<HostIndex> = None;
+
+        for node_index in self.topology.host_indices() {
+            if !self.is_visible(node_index) {
+                continue;
+            }
+            let sphere_center = self.node_location(node_index);
+            let difference: Vector3<f32> = ray_origin - sphere_center;
+            let difference_sqr = difference.dot(&difference);
+            let p = ray_direction.dot(&difference);
+
+            let determinant = p * p - difference_sqr + radius * radius;
+            let distance: f32;
+
+            if determinant.abs() < f32::EPSILON {
+                distance = ray_direction.scale(-1.0).dot(&difference);
+            } else if determinant < 0.0 {
+                continue;
+            } else {
+                let distance1 = ray_direction.scale(-1.0).dot(&difference) - determinant.sqrt();
+                let distance2 = ray_direction.scale(-1.0).dot(&difference) + determinant.sqrt();
+                distance = distance1.min(distance2);
+            }
+
+            // Negative distance is valid in principle, but not useful for object selection
+            if distance > 0.0 && distance < least_distance {
+                least_distance = distance;
+                nearest_node = Some(node_index);
+            }
+        }
+        nearest_node
+    }
+    /// Given a ray with origin and direction, find the nearest edge passing within EDGE_PICK_RADIUS of the ray, if it exists.
+    /// Ignores edges behind the ray origin.
+    pub fn find_nearest_edge(
+        &self,
+        ray_origin: Point3<f32>,
+        ray_direction: Vector3<f32>,
+    ) -> Option<LinkIndex> {
+        let graph = &self.topology.graph;
+
+        let mut least_distance = f32::MAX;
+        let mut nearest_edge: Option<LinkIndex> = None;
+
+        for edge_index in graph.edge_indices() {
+            let (source, target) = graph.edge_endpoints(edge_index).unwrap();
+            let (source, target) = match (self.displayed_node(source), self.displayed_node(target))
+            {
+                (Some(source), Some(target)) if source != target => (source, target),
+                _ => continue,
+            };
+            let start = self.node_location(source);
+            let segment = self.node_location(target) - start;
+            let offset = start - ray_origin;
+
+            // Closest points between the ray and the segment, with the segment parameter clamped to [0, 1].
+            let aa = segment.dot(&segment);
+            let ab = segment.dot(&ray_direction);
+            let bb = ray_direction.dot(&ray_direction);
+            let ac = segment.dot(&offset);
+            let bc = ray_direction.dot(&offset);
+            let denominator = aa * bb - ab * ab;
+
+            let s = if denominator.abs() < f32::EPSILON {
+                0.0
+            } else {
+                ((ab * bc - bb * ac) / denominator).clamp(0.0, 1.0)
+            };
+            let t = (ab * s + bc) / bb;
+            if t <= 0.0 {
+                continue;
+            }
+
+            let separation = (start + segment * s) - (ray_origin + ray_direction * t);
+            if separation.norm() < EDGE_PICK_RADIUS && t < least_distance {
+                least_distance = t;
+                nearest_edge = Some(edge_index);
+            }
+        }
+        nearest_edge
+    }
+}